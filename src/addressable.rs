@@ -0,0 +1,407 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An interval heap whose items can be accessed, removed and changed through handles.
+
+use std::fmt::{self, Debug};
+use std::mem;
+
+use compare::{Compare, Natural, natural};
+
+use super::{IntervalHeap, Iter, Observer, interval_heap_push, interval_heap_remove, update};
+
+/// A stable reference to an item of an `AddressableIntervalHeap`.
+///
+/// A handle stays valid until its item is removed from the heap. Handles of removed items
+/// are never confused with handles of items that were pushed later.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Handle {
+    slot: usize,
+    generation: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Slot {
+    /// The position of the slot's item in the heap, or `None` if the slot is free.
+    pos: Option<usize>,
+    generation: usize,
+}
+
+/// Keeps the slots' positions in sync with the items they refer to.
+struct Positions<'a> {
+    slot_at: &'a mut Vec<usize>,
+    slots: &'a mut Vec<Slot>,
+}
+
+impl<'a> Observer for Positions<'a> {
+    #[inline]
+    fn swapped(&mut self, a: usize, b: usize) {
+        self.slot_at.swap(a, b);
+        self.slots[self.slot_at[a]].pos = Some(a);
+        self.slots[self.slot_at[b]].pos = Some(b);
+    }
+}
+
+/// A double-ended priority queue whose items can be removed or changed after insertion.
+///
+/// Pushing an item returns a [`Handle`](struct.Handle.html) through which the item can later
+/// be accessed, removed or replaced in `O(log n)` time.
+///
+/// It is a logic error for an item to be modified in such a way that the
+/// item's ordering relative to any other item, as determined by the heap's
+/// comparator, changes while it is in the heap. This is normally only
+/// possible through `Cell`, `RefCell`, global state, I/O, or unsafe code.
+#[derive(Clone)]
+pub struct AddressableIntervalHeap<T, C: Compare<T> = Natural<T>> {
+    heap: IntervalHeap<T, C>,
+    /// The slot of the item at each position of the heap.
+    slot_at: Vec<usize>,
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+}
+
+impl<T, C: Compare<T> + Default> Default for AddressableIntervalHeap<T, C> {
+    #[inline]
+    fn default() -> AddressableIntervalHeap<T, C> {
+        Self::with_comparator(C::default())
+    }
+}
+
+impl<T: Ord> AddressableIntervalHeap<T> {
+    /// Returns an empty heap ordered according to the natural order of its items.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::AddressableIntervalHeap;
+    ///
+    /// let heap = AddressableIntervalHeap::<u32>::new();
+    /// assert!(heap.is_empty());
+    /// ```
+    pub fn new() -> AddressableIntervalHeap<T> { Self::with_comparator(natural()) }
+}
+
+impl<T, C: Compare<T>> AddressableIntervalHeap<T, C> {
+    /// Returns an empty heap ordered according to the given comparator.
+    pub fn with_comparator(cmp: C) -> AddressableIntervalHeap<T, C> {
+        AddressableIntervalHeap {
+            heap: IntervalHeap::with_comparator(cmp),
+            slot_at: vec![],
+            slots: vec![],
+            free: vec![],
+        }
+    }
+
+    /// Returns an iterator visiting all items in the heap in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, T> { self.heap.iter() }
+
+    /// Returns a reference to the smallest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn min(&self) -> Option<&T> { self.heap.min() }
+
+    /// Returns a reference to the greatest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn max(&self) -> Option<&T> { self.heap.max() }
+
+    /// Returns references to the smallest and greatest items in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn min_max(&self) -> Option<(&T, &T)> { self.heap.min_max() }
+
+    /// Returns the handle of the smallest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn min_handle(&self) -> Option<Handle> {
+        if self.is_empty() { None } else { Some(self.handle_at(0)) }
+    }
+
+    /// Returns the handle of the greatest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn max_handle(&self) -> Option<Handle> {
+        match self.len() {
+            0 => None,
+            1 => Some(self.handle_at(0)),
+            _ => Some(self.handle_at(1)),
+        }
+    }
+
+    /// Returns `true` if the item referred to by the given handle is in the heap.
+    pub fn contains(&self, handle: Handle) -> bool {
+        self.position(handle).is_some()
+    }
+
+    /// Returns a reference to the item referred to by the given handle.
+    ///
+    /// Returns `None` if the item is no longer in the heap.
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        self.position(handle).map(|pos| &self.heap.data[pos])
+    }
+
+    /// Pushes an item onto the heap and returns a handle to it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::AddressableIntervalHeap;
+    ///
+    /// let mut heap = AddressableIntervalHeap::new();
+    /// let a = heap.push(3);
+    /// let b = heap.push(5);
+    /// assert_eq!(heap.get(a), Some(&3));
+    /// assert_eq!(heap.remove(b), Some(5));
+    /// assert!(!heap.contains(b));
+    /// ```
+    pub fn push(&mut self, item: T) -> Handle {
+        debug_assert!(self.is_consistent());
+        let pos = self.heap.data.len();
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot].pos = Some(pos);
+                slot
+            }
+            None => {
+                self.slots.push(Slot { pos: Some(pos), generation: 0 });
+                self.slots.len() - 1
+            }
+        };
+        let handle = Handle { slot, generation: self.slots[slot].generation };
+        self.heap.data.push(item);
+        self.slot_at.push(slot);
+        {
+            let (heap, mut obs) = self.split();
+            interval_heap_push(&mut heap.data, &heap.cmp, &mut obs);
+        }
+        debug_assert!(self.is_consistent());
+        handle
+    }
+
+    /// Removes the item referred to by the given handle from the heap and returns it.
+    ///
+    /// Returns `None` if the item is no longer in the heap.
+    pub fn remove(&mut self, handle: Handle) -> Option<T> {
+        debug_assert!(self.is_consistent());
+        let item = self.position(handle).map(|pos| self.remove_at(pos));
+        debug_assert!(self.is_consistent());
+        item
+    }
+
+    /// Replaces the item referred to by the given handle with `item`, moving it to its new
+    /// place in the heap.
+    ///
+    /// Returns the replaced item, or gives `item` back as an error if the handle's item is no
+    /// longer in the heap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::AddressableIntervalHeap;
+    ///
+    /// let mut heap = AddressableIntervalHeap::new();
+    /// let a = heap.push(3);
+    /// heap.push(5);
+    /// assert_eq!(heap.update(a, 7), Ok(3));
+    /// assert_eq!(heap.max(), Some(&7));
+    /// assert_eq!(heap.max_handle(), Some(a));
+    /// ```
+    pub fn update(&mut self, handle: Handle, item: T) -> Result<T, T> {
+        debug_assert!(self.is_consistent());
+        let pos = match self.position(handle) {
+            Some(pos) => pos,
+            None => return Err(item),
+        };
+        let old = mem::replace(&mut self.heap.data[pos], item);
+        self.sift(pos);
+        debug_assert!(self.is_consistent());
+        Ok(old)
+    }
+
+    /// Changes the item referred to by the given handle in place through `f`, moving it to
+    /// its new place in the heap.
+    ///
+    /// Returns what `f` returns, or `None` if the handle's item is no longer in the heap.
+    pub(crate) fn update_with<R, F>(&mut self, handle: Handle, f: F) -> Option<R>
+            where F: FnOnce(&mut T) -> R {
+        debug_assert!(self.is_consistent());
+        let pos = self.position(handle)?;
+        let res = f(&mut self.heap.data[pos]);
+        self.sift(pos);
+        debug_assert!(self.is_consistent());
+        Some(res)
+    }
+
+    /// Removes the smallest item from the heap and returns it.
+    ///
+    /// Returns `None` if the heap was empty.
+    pub fn pop_min(&mut self) -> Option<T> {
+        debug_assert!(self.is_consistent());
+        let min = if self.is_empty() { None } else { Some(self.remove_at(0)) };
+        debug_assert!(self.is_consistent());
+        min
+    }
+
+    /// Removes the greatest item from the heap and returns it.
+    ///
+    /// Returns `None` if the heap was empty.
+    pub fn pop_max(&mut self) -> Option<T> {
+        debug_assert!(self.is_consistent());
+        let max = match self.len() {
+            0 => None,
+            1 => Some(self.remove_at(0)),
+            _ => Some(self.remove_at(1)),
+        };
+        debug_assert!(self.is_consistent());
+        max
+    }
+
+    /// Returns the number of items in the heap.
+    pub fn len(&self) -> usize { self.heap.len() }
+
+    /// Returns `true` if the heap contains no items.
+    pub fn is_empty(&self) -> bool { self.heap.is_empty() }
+
+    /// Removes all items from the heap.
+    ///
+    /// All handles to the removed items become invalid.
+    pub fn clear(&mut self) {
+        while let Some(slot) = self.slot_at.pop() {
+            self.release(slot);
+        }
+        self.heap.clear();
+    }
+
+    /// Consumes the heap and returns the underlying `IntervalHeap`.
+    pub fn into_heap(self) -> IntervalHeap<T, C> { self.heap }
+
+    /// Moves the changed item at `pos` to its new place in the heap.
+    fn sift(&mut self, pos: usize) {
+        let (heap, mut obs) = self.split();
+        update(&mut heap.data, &heap.cmp, pos, &mut obs);
+    }
+
+    fn handle_at(&self, pos: usize) -> Handle {
+        let slot = self.slot_at[pos];
+        Handle { slot, generation: self.slots[slot].generation }
+    }
+
+    fn position(&self, handle: Handle) -> Option<usize> {
+        match self.slots.get(handle.slot) {
+            Some(slot) if slot.generation == handle.generation => slot.pos,
+            _ => None,
+        }
+    }
+
+    /// Removes the item at the given position, restoring the heap's invariants for the
+    /// remaining items.
+    fn remove_at(&mut self, pos: usize) -> T {
+        let item = {
+            let (heap, mut obs) = self.split();
+            interval_heap_remove(&mut heap.data, &heap.cmp, pos, &mut obs)
+        };
+        let slot = self.slot_at.pop().unwrap();
+        self.release(slot);
+        item
+    }
+
+    fn release(&mut self, slot: usize) {
+        let slot_ref = &mut self.slots[slot];
+        slot_ref.pos = None;
+        slot_ref.generation = slot_ref.generation.wrapping_add(1);
+        self.free.push(slot);
+    }
+
+    fn split(&mut self) -> (&mut IntervalHeap<T, C>, Positions<'_>) {
+        (&mut self.heap, Positions { slot_at: &mut self.slot_at, slots: &mut self.slots })
+    }
+
+    /// Checks if the heap is valid and every slot knows its item's position.
+    fn is_consistent(&self) -> bool {
+        self.heap.is_valid() &&
+        self.slot_at.len() == self.heap.len() &&
+        self.slot_at.iter().enumerate().all(|(pos, &slot)| self.slots[slot].pos == Some(pos))
+    }
+}
+
+impl<T: Debug, C: Compare<T>> Debug for AddressableIntervalHeap<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.heap.fmt(f)
+    }
+}
+
+impl<'a, T, C: Compare<T>> IntoIterator for &'a AddressableIntervalHeap<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Iter<'a, T> { self.iter() }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{thread_rng, Rng};
+    use super::AddressableIntervalHeap;
+
+    #[test]
+    fn fuzz_remove_update() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let mut heap = AddressableIntervalHeap::new();
+            let mut handles = vec![];
+            for _ in 0..200 {
+                match rng.gen_range(0, 5) {
+                    0 | 1 => {
+                        let x: u32 = rng.gen_range(0, 100);
+                        handles.push((heap.push(x), x));
+                    }
+                    2 if !handles.is_empty() => {
+                        let i = rng.gen_range(0, handles.len());
+                        let (h, x) = handles.swap_remove(i);
+                        assert_eq!(heap.remove(h), Some(x));
+                        assert!(!heap.contains(h));
+                        assert_eq!(heap.remove(h), None);
+                    }
+                    3 if !handles.is_empty() => {
+                        let i = rng.gen_range(0, handles.len());
+                        let y = rng.gen_range(0, 100);
+                        assert_eq!(heap.update(handles[i].0, y), Ok(handles[i].1));
+                        handles[i].1 = y;
+                    }
+                    _ => {
+                        let min = heap.min_handle();
+                        if let Some(x) = heap.pop_min() {
+                            let i = handles.iter().position(|&(h, _)| Some(h) == min).unwrap();
+                            assert_eq!(handles.swap_remove(i).1, x);
+                        }
+                    }
+                }
+                assert!(heap.is_consistent());
+                assert_eq!(heap.len(), handles.len());
+                for &(h, x) in &handles {
+                    assert_eq!(heap.get(h), Some(&x));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_stale_handle() {
+        let mut heap = AddressableIntervalHeap::new();
+        let a = heap.push(1);
+        assert_eq!(heap.pop_max(), Some(1));
+        let b = heap.push(2);
+        assert!(!heap.contains(a));
+        assert_eq!(heap.get(a), None);
+        assert_eq!(heap.update(a, 3), Err(3));
+        assert_eq!(heap.get(b), Some(&2));
+        heap.clear();
+        assert!(!heap.contains(b));
+        assert!(heap.is_empty());
+    }
+}
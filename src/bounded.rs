@@ -0,0 +1,260 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An interval heap holding a bounded number of items.
+
+use std::cmp;
+use std::fmt::{self, Debug};
+
+use compare::{Compare, Natural, natural};
+
+use super::{IntervalHeap, Iter};
+
+/// Determines which item a full `BoundedIntervalHeap` gives up when another item is pushed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EvictionPolicy {
+    /// Evicts the smallest item, keeping the greatest items pushed so far.
+    DropMin,
+    /// Evicts the greatest item, keeping the smallest items pushed so far.
+    DropMax,
+    /// Rejects new items while the heap is full.
+    RejectNew,
+}
+
+/// A double-ended priority queue holding at most a fixed number of items.
+///
+/// Once the heap is full, each push evicts an item according to the heap's
+/// [`EvictionPolicy`](enum.EvictionPolicy.html), which makes it a natural fit for keeping the
+/// top-k or bottom-k items of a stream. Evicting an item takes a single `O(log n)` pass
+/// through the heap (see [`IntervalHeap::push_pop_min`](
+/// struct.IntervalHeap.html#method.push_pop_min)).
+///
+/// It is a logic error for an item to be modified in such a way that the
+/// item's ordering relative to any other item, as determined by the heap's
+/// comparator, changes while it is in the heap. This is normally only
+/// possible through `Cell`, `RefCell`, global state, I/O, or unsafe code.
+#[derive(Clone)]
+pub struct BoundedIntervalHeap<T, C: Compare<T> = Natural<T>> {
+    heap: IntervalHeap<T, C>,
+    capacity: usize,
+    policy: EvictionPolicy,
+}
+
+impl<T: Ord> BoundedIntervalHeap<T> {
+    /// Returns an empty heap holding at most `capacity` items, ordered according to the
+    /// natural order of its items.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::{BoundedIntervalHeap, EvictionPolicy};
+    ///
+    /// let mut top3 = BoundedIntervalHeap::new(3, EvictionPolicy::DropMin);
+    /// for x in vec![5, 1, 8, 3, 9, 2] {
+    ///     top3.push(x);
+    /// }
+    /// assert_eq!(top3.into_sorted_vec(), [5, 8, 9]);
+    /// ```
+    pub fn new(capacity: usize, policy: EvictionPolicy) -> BoundedIntervalHeap<T> {
+        Self::with_comparator(capacity, policy, natural())
+    }
+}
+
+impl<T, C: Compare<T>> BoundedIntervalHeap<T, C> {
+    /// Returns an empty heap holding at most `capacity` items, ordered according to the given
+    /// comparator.
+    ///
+    /// The capacity is only a limit: the heap grows as items are pushed, so a large capacity
+    /// does not allocate any memory up front.
+    pub fn with_comparator(capacity: usize, policy: EvictionPolicy, cmp: C)
+                           -> BoundedIntervalHeap<T, C> {
+        BoundedIntervalHeap {
+            heap: IntervalHeap::with_comparator(cmp),
+            capacity,
+            policy,
+        }
+    }
+
+    /// Returns the maximum number of items the heap holds.
+    pub fn capacity(&self) -> usize { self.capacity }
+
+    /// Returns the heap's eviction policy.
+    pub fn policy(&self) -> EvictionPolicy { self.policy }
+
+    /// Returns an iterator visiting all items in the heap in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, T> { self.heap.iter() }
+
+    /// Returns a reference to the smallest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn min(&self) -> Option<&T> { self.heap.min() }
+
+    /// Returns a reference to the greatest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn max(&self) -> Option<&T> { self.heap.max() }
+
+    /// Returns references to the smallest and greatest items in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn min_max(&self) -> Option<(&T, &T)> { self.heap.min_max() }
+
+    /// Pushes an item onto the heap.
+    ///
+    /// If the heap is full, an item is evicted according to the heap's eviction policy and
+    /// returned. This may be `item` itself, if it would have been evicted right away or the
+    /// policy rejects new items.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::{BoundedIntervalHeap, EvictionPolicy};
+    ///
+    /// let mut heap = BoundedIntervalHeap::new(2, EvictionPolicy::DropMax);
+    /// assert_eq!(heap.push(4), None);
+    /// assert_eq!(heap.push(2), None);
+    /// assert_eq!(heap.push(3), Some(4));
+    /// assert_eq!(heap.push(5), Some(5));
+    /// assert_eq!(heap.min_max(), Some((&2, &3)));
+    /// ```
+    pub fn push(&mut self, item: T) -> Option<T> {
+        if self.heap.len() < self.capacity {
+            self.heap.push(item);
+            return None;
+        }
+        Some(match self.policy {
+            EvictionPolicy::DropMin => self.heap.push_pop_min(item),
+            EvictionPolicy::DropMax => self.heap.push_pop_max(item),
+            EvictionPolicy::RejectNew => item,
+        })
+    }
+
+    /// Removes the smallest item from the heap and returns it.
+    ///
+    /// Returns `None` if the heap was empty.
+    pub fn pop_min(&mut self) -> Option<T> { self.heap.pop_min() }
+
+    /// Removes the greatest item from the heap and returns it.
+    ///
+    /// Returns `None` if the heap was empty.
+    pub fn pop_max(&mut self) -> Option<T> { self.heap.pop_max() }
+
+    /// Returns the number of items in the heap.
+    pub fn len(&self) -> usize { self.heap.len() }
+
+    /// Returns `true` if the heap contains no items.
+    pub fn is_empty(&self) -> bool { self.heap.is_empty() }
+
+    /// Returns `true` if the heap holds as many items as its capacity allows.
+    pub fn is_full(&self) -> bool { self.heap.len() >= self.capacity }
+
+    /// Removes all items from the heap.
+    pub fn clear(&mut self) { self.heap.clear(); }
+
+    /// Consumes the heap and returns the underlying `IntervalHeap`.
+    pub fn into_heap(self) -> IntervalHeap<T, C> { self.heap }
+
+    /// Consumes the heap and returns its items as a vector in sorted (ascending) order.
+    pub fn into_sorted_vec(self) -> Vec<T> { self.heap.into_sorted_vec() }
+}
+
+impl<T: Debug, C: Compare<T>> Debug for BoundedIntervalHeap<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.heap.fmt(f)
+    }
+}
+
+impl<T, C: Compare<T>> Extend<T> for BoundedIntervalHeap<T, C> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        let (lower, _) = iter.size_hint();
+        self.heap.reserve(cmp::min(lower, self.capacity - self.heap.len()));
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+impl<'a, T: 'a + Copy, C: Compare<T>> Extend<&'a T> for BoundedIntervalHeap<T, C> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().cloned());
+    }
+}
+
+impl<T, C: Compare<T>> IntoIterator for BoundedIntervalHeap<T, C> {
+    type Item = T;
+    type IntoIter = ::IntoIter<T>;
+    fn into_iter(self) -> ::IntoIter<T> { self.heap.into_iter() }
+}
+
+impl<'a, T, C: Compare<T>> IntoIterator for &'a BoundedIntervalHeap<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Iter<'a, T> { self.iter() }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{thread_rng, Rng};
+    use super::{BoundedIntervalHeap, EvictionPolicy};
+
+    #[test]
+    fn fuzz_push() {
+        let mut rng = thread_rng();
+        for &policy in &[EvictionPolicy::DropMin, EvictionPolicy::DropMax,
+                         EvictionPolicy::RejectNew] {
+            for capacity in 0..20 {
+                let mut heap = BoundedIntervalHeap::new(capacity, policy);
+                let mut all = vec![];
+                let mut evicted = vec![];
+                for _ in 0..50 {
+                    let x: u32 = rng.gen_range(0, 100);
+                    all.push(x);
+                    evicted.extend(heap.push(x));
+                    assert!(heap.heap.is_valid());
+                    assert_eq!(heap.len(), all.len().min(capacity));
+                }
+                let mut kept = heap.into_sorted_vec();
+                match policy {
+                    EvictionPolicy::DropMin => {
+                        all.sort();
+                        let split = all.len() - kept.len();
+                        assert_eq!(kept, &all[split..]);
+                    }
+                    EvictionPolicy::DropMax => {
+                        all.sort();
+                        assert_eq!(kept, &all[..kept.len()]);
+                    }
+                    EvictionPolicy::RejectNew => {
+                        let mut first = all[..kept.len()].to_vec();
+                        first.sort();
+                        assert_eq!(kept, first);
+                    }
+                }
+                kept.extend(evicted);
+                kept.sort();
+                all.sort();
+                assert_eq!(kept, all);
+            }
+        }
+    }
+    #[test]
+    fn test_large_capacity() {
+        let mut heap = BoundedIntervalHeap::<u32>::new(usize::MAX / 2, EvictionPolicy::DropMin);
+        assert!(heap.heap.capacity() < 1000);
+        heap.extend(0..1000);
+        assert_eq!(heap.len(), 1000);
+
+        let mut top3 = BoundedIntervalHeap::<u32>::new(3, EvictionPolicy::DropMin);
+        top3.extend(&[5, 1, 8, 3, 9, 2]);
+        assert!(top3.heap.capacity() < 6);
+        assert_eq!(top3.into_sorted_vec(), [5, 8, 9]);
+    }
+}
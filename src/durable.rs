@@ -0,0 +1,504 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An interval heap that records its changes in a write-ahead log.
+
+use std::fmt::{self, Debug};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::slice;
+
+use compare::{Compare, Natural};
+
+use super::{IntervalHeap, heapify};
+use snapshot::{self, Pod};
+
+const PUSH: u8 = 0;
+const POP_MIN: u8 = 1;
+const POP_MAX: u8 = 2;
+const CLEAR: u8 = 3;
+
+/// The length of a record's tag and payload length, which precede its payload.
+const RECORD_HEADER_LEN: usize = 5;
+/// The length of a record's checksum, which follows its payload.
+const RECORD_CHECKSUM_LEN: usize = 8;
+
+/// An interval heap whose contents survive crashes of the process using it.
+///
+/// The heap lives in a directory of its own. Every change is appended to a log file in that
+/// directory before it is applied, and opening the directory again replays the log. Every
+/// so often (see [`set_compaction_interval`](#method.set_compaction_interval)), the heap is
+/// compacted: its items are written to a [snapshot](
+/// struct.IntervalHeap.html#method.write_snapshot) and a new, empty log is started.
+///
+/// Records are handed to the operating system as soon as they are written, which protects
+/// them against the process crashing. To also protect them against the system crashing,
+/// call [`sync`](#method.sync).
+///
+/// Each record consists of a one-byte tag, the length of its payload as a little-endian
+/// `u32`, the payload, and a [checksum](struct.IntervalHeap.html#method.write_snapshot) of
+/// all of these as a little-endian `u64`. When the heap is opened, the log is replayed up to
+/// the first record that is incomplete or does not match its checksum, and cut off there.
+/// This discards a record the process crashed while writing, as well as any garbage, such as
+/// zeros, the file system may leave at the end of the log after a system crash. If writing a
+/// record fails otherwise, the record is cut off the log again; should that fail, too, the
+/// heap refuses all further changes.
+///
+/// Only one heap may use a directory at a time; see
+/// [`open_with_comparator`](#method.open_with_comparator).
+///
+/// Compaction happens as part of the change that makes it due. If it fails, the change
+/// itself still succeeds, as it is already in the log, and compaction is retried with the
+/// next change. Call [`compact`](#method.compact) directly to learn about its errors.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate interval_heap;
+/// # extern crate tempfile;
+/// # fn main() {
+/// use interval_heap::DurableIntervalHeap;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// {
+///     let mut heap = DurableIntervalHeap::<u64>::open(dir.path()).unwrap();
+///     heap.push(3).unwrap();
+///     heap.push(1).unwrap();
+///     heap.push(4).unwrap();
+///     assert_eq!(heap.pop_max().unwrap(), Some(4));
+/// }
+/// let heap = DurableIntervalHeap::<u64>::open(dir.path()).unwrap();
+/// assert_eq!(heap.heap().min_max(), Some((&1, &3)));
+/// # }
+/// ```
+pub struct DurableIntervalHeap<T: Pod, C: Compare<T> = Natural<T>> {
+    heap: IntervalHeap<T, C>,
+    dir: PathBuf,
+    /// Counts the compactions, naming the current snapshot and log files.
+    generation: u64,
+    log: File,
+    /// The length of the complete records in the current log.
+    log_len: u64,
+    /// The number of records in the current log.
+    records: usize,
+    compaction_interval: usize,
+    /// Set if a failed write could not be cut off the log.
+    poisoned: bool,
+}
+
+fn snapshot_path(dir: &Path, generation: u64) -> PathBuf {
+    dir.join(format!("heap-{}.snapshot", generation))
+}
+
+fn log_path(dir: &Path, generation: u64) -> PathBuf {
+    dir.join(format!("heap-{}.log", generation))
+}
+
+/// Splits a file name of the form `heap-<generation>.<extension>`.
+fn parse_file_name(name: &str) -> Option<(u64, &str)> {
+    let rest = name.strip_prefix("heap-")?;
+    let dot = rest.find('.')?;
+    let generation = rest[..dot].parse().ok()?;
+    Some((generation, &rest[dot + 1..]))
+}
+
+/// Returns the generation of the latest snapshot in `dir`, if any.
+fn latest_snapshot(dir: &Path) -> io::Result<Option<u64>> {
+    let mut latest = None;
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        if let Some((generation, "snapshot")) = name.to_str().and_then(parse_file_name) {
+            latest = latest.max(Some(generation));
+        }
+    }
+    Ok(latest)
+}
+
+/// Flushes the directory's entries to the storage device, so that files created or renamed
+/// in it survive a system crash.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> { File::open(dir)?.sync_all() }
+
+#[cfg(not(unix))]
+fn sync_dir(_: &Path) -> io::Result<()> { Ok(()) }
+
+/// Removes the files of all generations but `generation`, along with any snapshot left over
+/// from an interrupted compaction.
+fn remove_stale_files(dir: &Path, generation: u64) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let stale = match entry.file_name().to_str().and_then(parse_file_name) {
+            Some((g, "snapshot")) | Some((g, "log")) => g != generation,
+            Some((_, "snapshot.tmp")) => true,
+            _ => false,
+        };
+        if stale { fs::remove_file(entry.path())?; }
+    }
+    Ok(())
+}
+
+/// Encodes a record with the given tag and payload.
+fn encode_record(tag: u8, payload: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len() + RECORD_CHECKSUM_LEN);
+    record.push(tag);
+    record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    record.extend_from_slice(payload);
+    let sum = snapshot::checksum(&record);
+    record.extend_from_slice(&sum.to_le_bytes());
+    record
+}
+
+/// Decodes the record at the start of `bytes`, returning its tag, its payload and its
+/// length. Returns `None` if the record is incomplete or does not match its checksum.
+fn decode_record(bytes: &[u8]) -> Option<(u8, &[u8], usize)> {
+    if bytes.len() < RECORD_HEADER_LEN { return None; }
+    let mut buf = [0; 4];
+    buf.copy_from_slice(&bytes[1..RECORD_HEADER_LEN]);
+    let payload_end = RECORD_HEADER_LEN.checked_add(u32::from_le_bytes(buf) as usize)?;
+    let end = payload_end.checked_add(RECORD_CHECKSUM_LEN)?;
+    if bytes.len() < end { return None; }
+    let mut buf = [0; RECORD_CHECKSUM_LEN];
+    buf.copy_from_slice(&bytes[payload_end..end]);
+    if snapshot::checksum(&bytes[..payload_end]) != u64::from_le_bytes(buf) { return None; }
+    Some((bytes[0], &bytes[RECORD_HEADER_LEN..payload_end], end))
+}
+
+/// Applies the records in `bytes` to the heap, returning the length of the valid records and
+/// their number. Replaying stops at the first record that is incomplete or does not match its
+/// checksum.
+fn replay<T: Pod, C: Compare<T>>(heap: &mut IntervalHeap<T, C>, bytes: &[u8])
+                                 -> io::Result<(usize, usize)> {
+    let mut pos = 0;
+    let mut records = 0;
+    while let Some((tag, payload, len)) = decode_record(&bytes[pos..]) {
+        match (tag, payload.len()) {
+            (PUSH, n) if n == mem::size_of::<T>() => {
+                // SAFETY: The payload holds exactly one item, and every bit pattern is a
+                // valid `Pod` value.
+                let item = unsafe { ptr::read_unaligned(payload.as_ptr() as *const T) };
+                heap.push(item);
+            }
+            (POP_MIN, 0) => { heap.pop_min(); }
+            (POP_MAX, 0) => { heap.pop_max(); }
+            (CLEAR, 0) => heap.clear(),
+            _ => return Err(snapshot::invalid_data("corrupted log record")),
+        }
+        pos += len;
+        records += 1;
+    }
+    Ok((pos, records))
+}
+
+impl<T: Pod, C: Compare<T> + Default> DurableIntervalHeap<T, C> {
+    /// Opens the heap stored in the given directory, creating the directory if it does not
+    /// exist yet.
+    ///
+    /// See [`open_with_comparator`](#method.open_with_comparator) for details.
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<DurableIntervalHeap<T, C>> {
+        Self::open_with_comparator(dir, C::default())
+    }
+}
+
+impl<T: Pod, C: Compare<T>> DurableIntervalHeap<T, C> {
+    /// Opens the heap stored in the given directory, creating the directory if it does not
+    /// exist yet.
+    ///
+    /// As with [`IntervalHeap::read_snapshot`](
+    /// struct.IntervalHeap.html#method.read_snapshot), a snapshot written with the same
+    /// ordering is restored after checking that its items are in heap order, and rebuilt in
+    /// `O(n)` time otherwise.
+    ///
+    /// The directory must not be opened by more than one heap at a time, whether in the same
+    /// process or in another one. The heaps would append their records to the same log, which
+    /// then no longer describes either heap.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is zero-sized.
+    pub fn open_with_comparator<P: AsRef<Path>>(dir: P, cmp: C)
+                                                -> io::Result<DurableIntervalHeap<T, C>> {
+        assert!(mem::size_of::<T>() != 0, "zero-sized items are not supported");
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let mut heap = IntervalHeap::with_comparator(cmp);
+        let generation = match latest_snapshot(&dir)? {
+            Some(generation) => {
+                let file = File::open(snapshot_path(&dir, generation))?;
+                heap.data = snapshot::read_items(BufReader::new(file))?;
+                if !heap.is_valid() {
+                    heapify(&mut heap.data, &heap.cmp);
+                }
+                generation
+            }
+            None => 0,
+        };
+        let mut log = OpenOptions::new().read(true).append(true).create(true)
+                                        .open(log_path(&dir, generation))?;
+        let mut bytes = vec![];
+        log.read_to_end(&mut bytes)?;
+        let (len, records) = replay(&mut heap, &bytes)?;
+        if len < bytes.len() { log.set_len(len as u64)?; }
+        remove_stale_files(&dir, generation)?;
+        Ok(DurableIntervalHeap {
+            heap,
+            dir,
+            generation,
+            log,
+            log_len: len as u64,
+            records,
+            compaction_interval: 4096,
+            poisoned: false,
+        })
+    }
+
+    /// Returns the underlying `IntervalHeap`.
+    pub fn heap(&self) -> &IntervalHeap<T, C> { &self.heap }
+
+    /// Returns a reference to the smallest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn min(&self) -> Option<&T> { self.heap.min() }
+
+    /// Returns a reference to the greatest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn max(&self) -> Option<&T> { self.heap.max() }
+
+    /// Returns the number of items in the heap.
+    pub fn len(&self) -> usize { self.heap.len() }
+
+    /// Returns `true` if the heap contains no items.
+    pub fn is_empty(&self) -> bool { self.heap.is_empty() }
+
+    /// Returns the number of records after which the heap is compacted.
+    pub fn compaction_interval(&self) -> usize { self.compaction_interval }
+
+    /// Sets the number of records after which the heap is compacted. The default is 4096.
+    ///
+    /// Compacting writes all items in the heap, so it should happen less often the larger
+    /// the heap is.
+    pub fn set_compaction_interval(&mut self, interval: usize) {
+        self.compaction_interval = interval;
+    }
+
+    /// Pushes an item onto the heap.
+    pub fn push(&mut self, item: T) -> io::Result<()> {
+        self.record(PUSH, snapshot::as_bytes(slice::from_ref(&item)))?;
+        self.heap.push(item);
+        self.compact_if_due();
+        Ok(())
+    }
+
+    /// Removes the smallest item from the heap and returns it.
+    ///
+    /// Returns `None` if the heap was empty.
+    pub fn pop_min(&mut self) -> io::Result<Option<T>> {
+        if self.heap.is_empty() { return Ok(None); }
+        self.record(POP_MIN, &[])?;
+        let min = self.heap.pop_min();
+        self.compact_if_due();
+        Ok(min)
+    }
+
+    /// Removes the greatest item from the heap and returns it.
+    ///
+    /// Returns `None` if the heap was empty.
+    pub fn pop_max(&mut self) -> io::Result<Option<T>> {
+        if self.heap.is_empty() { return Ok(None); }
+        self.record(POP_MAX, &[])?;
+        let max = self.heap.pop_max();
+        self.compact_if_due();
+        Ok(max)
+    }
+
+    /// Removes all items from the heap.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.record(CLEAR, &[])?;
+        self.heap.clear();
+        self.compact_if_due();
+        Ok(())
+    }
+
+    /// Writes the heap's items to a new snapshot and starts a new, empty log.
+    ///
+    /// If this fails, the heap can still be opened from its previous snapshot and log, and
+    /// keeps recording changes in the previous log.
+    pub fn compact(&mut self) -> io::Result<()> {
+        if self.poisoned { return Err(poisoned()); }
+        let generation = self.generation + 1;
+        let tmp = self.dir.join(format!("heap-{}.snapshot.tmp", generation));
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        self.heap.write_snapshot(&mut writer)?;
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        // Once the snapshot is in place, it takes precedence over the previous snapshot and
+        // log, so a crash from here on cannot replay any record twice.
+        let log = File::create(log_path(&self.dir, generation))?;
+        fs::rename(&tmp, snapshot_path(&self.dir, generation))?;
+        self.log = log;
+        self.log_len = 0;
+        self.generation = generation;
+        self.records = 0;
+        sync_dir(&self.dir)?;
+        remove_stale_files(&self.dir, generation)
+    }
+
+    /// Flushes all records to the storage device.
+    pub fn sync(&self) -> io::Result<()> { self.log.sync_data() }
+
+    /// Appends a record with the given tag and payload to the log.
+    ///
+    /// If the record cannot be written completely, it is cut off again, as replaying would
+    /// otherwise stop at it and drop any later records.
+    fn record(&mut self, tag: u8, payload: &[u8]) -> io::Result<()> {
+        if self.poisoned { return Err(poisoned()); }
+        let record = encode_record(tag, payload);
+        if let Err(e) = self.log.write_all(&record) {
+            if self.log.set_len(self.log_len).is_err() { self.poisoned = true; }
+            return Err(e);
+        }
+        self.log_len += record.len() as u64;
+        self.records += 1;
+        Ok(())
+    }
+
+    /// Compacts the heap if it is due. A failed compaction is retried with the next change.
+    fn compact_if_due(&mut self) {
+        if self.records >= self.compaction_interval {
+            let _ = self.compact();
+        }
+    }
+}
+
+fn poisoned() -> io::Error {
+    io::Error::other("a failed write could not be cut off the log")
+}
+
+impl<T: Pod + Debug, C: Compare<T>> Debug for DurableIntervalHeap<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.heap.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate tempfile;
+
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use rand::{thread_rng, Rng};
+    use super::super::IntervalHeap;
+    use super::{DurableIntervalHeap, log_path, snapshot_path};
+
+    #[test]
+    fn fuzz_reopen() {
+        let mut rng = thread_rng();
+        let dir = tempfile::tempdir().unwrap();
+        let mut heap = DurableIntervalHeap::<u32>::open(dir.path()).unwrap();
+        heap.set_compaction_interval(50);
+        let mut model = IntervalHeap::new();
+        for _ in 0..1000 {
+            match rng.gen_range(0, 10) {
+                0..=4 => {
+                    let x = rng.gen_range(0, 100);
+                    heap.push(x).unwrap();
+                    model.push(x);
+                }
+                5 | 6 => assert_eq!(heap.pop_min().unwrap(), model.pop_min()),
+                7 | 8 => assert_eq!(heap.pop_max().unwrap(), model.pop_max()),
+                _ if rng.gen_weighted_bool(10) => {
+                    heap.clear().unwrap();
+                    model.clear();
+                }
+                _ => {
+                    drop(heap);
+                    heap = DurableIntervalHeap::open(dir.path()).unwrap();
+                    heap.set_compaction_interval(50);
+                }
+            }
+            assert_eq!(heap.heap().data, model.data);
+        }
+        // Only the latest snapshot and log remain.
+        assert!(fs::read_dir(dir.path()).unwrap().count() <= 2);
+    }
+
+    #[test]
+    fn test_torn_record() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let mut heap = DurableIntervalHeap::<u64>::open(dir.path()).unwrap();
+            heap.push(7).unwrap();
+            heap.push(2).unwrap();
+        }
+        let path = log_path(dir.path(), 0);
+        let len = fs::metadata(&path).unwrap().len();
+        // A push record whose item was only partially written.
+        OpenOptions::new().append(true).open(&path).unwrap()
+                          .write_all(&[0, 8, 0, 0, 0, 1, 2]).unwrap();
+        {
+            let mut heap = DurableIntervalHeap::<u64>::open(dir.path()).unwrap();
+            assert_eq!(heap.heap().min_max(), Some((&2, &7)));
+            assert_eq!(fs::metadata(&path).unwrap().len(), len);
+            heap.push(5).unwrap();
+        }
+        let heap = DurableIntervalHeap::<u64>::open(dir.path()).unwrap();
+        assert_eq!(heap.heap().clone().into_sorted_vec(), [2, 5, 7]);
+    }
+
+    #[test]
+    fn test_garbage_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let mut heap = DurableIntervalHeap::<u64>::open(dir.path()).unwrap();
+            heap.push(7).unwrap();
+            heap.sync().unwrap();
+        }
+        let path = log_path(dir.path(), 0);
+        let len = fs::metadata(&path).unwrap().len();
+        // The zeros a file system may leave behind after a power loss.
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(&[0; 27]).unwrap();
+        {
+            let mut heap = DurableIntervalHeap::<u64>::open(dir.path()).unwrap();
+            assert_eq!(heap.heap().data, [7]);
+            assert_eq!(fs::metadata(&path).unwrap().len(), len);
+            heap.push(3).unwrap();
+            heap.pop_max().unwrap();
+        }
+        // A record that is complete, but damaged, ends the log as well.
+        let mut bytes = fs::read(&path).unwrap();
+        let at = bytes.len() - 10;
+        bytes[at] ^= 1;
+        fs::write(&path, &bytes).unwrap();
+        let heap = DurableIntervalHeap::<u64>::open(dir.path()).unwrap();
+        assert_eq!(heap.heap().clone().into_sorted_vec(), [3, 7]);
+    }
+
+    #[test]
+    fn test_failed_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut heap = DurableIntervalHeap::<u32>::open(dir.path()).unwrap();
+        heap.set_compaction_interval(2);
+        // A directory in place of the new snapshot makes compaction fail.
+        let blocker = dir.path().join("heap-1.snapshot.tmp");
+        fs::create_dir(&blocker).unwrap();
+        heap.push(10).unwrap();
+        heap.push(20).unwrap();
+        assert!(heap.compact().is_err());
+        assert_eq!(heap.pop_min().unwrap(), Some(10));
+        fs::remove_dir(&blocker).unwrap();
+        heap.push(30).unwrap();
+        assert!(snapshot_path(dir.path(), 1).exists());
+        drop(heap);
+        let heap = DurableIntervalHeap::<u32>::open(dir.path()).unwrap();
+        assert_eq!(heap.heap().clone().into_sorted_vec(), [20, 30]);
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An interval heap ordered by keys that are computed once per item.
+
+use std::cmp::Ordering;
+use std::fmt::{self, Debug};
+use std::marker::PhantomData;
+use std::slice;
+
+use compare::Compare;
+
+use super::IntervalHeap;
+
+/// Compares `(key, item)` pairs by their keys only.
+struct ByKey<K>(PhantomData<fn(&K)>);
+
+impl<K> Clone for ByKey<K> {
+    fn clone(&self) -> ByKey<K> { ByKey(PhantomData) }
+}
+
+impl<K: Ord, T> Compare<(K, T)> for ByKey<K> {
+    fn compare(&self, a: &(K, T), b: &(K, T)) -> Ordering { a.0.cmp(&b.0) }
+}
+
+/// A double-ended priority queue ordered by keys extracted from its items.
+///
+/// Unlike a heap created through [`IntervalHeap::with_key`](
+/// struct.IntervalHeap.html#method.with_key), which extracts the keys anew for every
+/// comparison, this heap extracts an item's key exactly once when the item is pushed and
+/// stores it alongside the item.
+///
+/// Acquire through [`IntervalHeap::with_cached_key`](
+/// struct.IntervalHeap.html#method.with_cached_key).
+///
+/// # Examples
+///
+/// ```
+/// use interval_heap::IntervalHeap;
+///
+/// let mut heap = IntervalHeap::with_cached_key(|s: &String| s.to_lowercase());
+/// heap.push("Banana".to_string());
+/// heap.push("apple".to_string());
+/// heap.push("Cherry".to_string());
+/// assert_eq!(heap.min().map(|s| &s[..]), Some("apple"));
+/// assert_eq!(heap.pop_max().as_ref().map(|s| &s[..]), Some("Cherry"));
+/// ```
+#[derive(Clone)]
+pub struct CachedKeyIntervalHeap<T, K: Ord, F> {
+    heap: IntervalHeap<(K, T), ByKey<K>>,
+    key: F,
+}
+
+impl<T, K: Ord, F: Fn(&T) -> K> CachedKeyIntervalHeap<T, K, F> {
+    /// Returns an empty heap ordered by the keys `key` extracts from its items.
+    pub fn new(key: F) -> CachedKeyIntervalHeap<T, K, F> {
+        CachedKeyIntervalHeap { heap: IntervalHeap::with_comparator(ByKey(PhantomData)), key }
+    }
+
+    /// Returns an iterator visiting all items in the heap in arbitrary order.
+    pub fn iter(&self) -> CachedKeyIter<'_, K, T> { CachedKeyIter(self.heap.data.iter()) }
+
+    /// Returns a reference to the smallest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn min(&self) -> Option<&T> { self.heap.min().map(|(_, item)| item) }
+
+    /// Returns a reference to the greatest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn max(&self) -> Option<&T> { self.heap.max().map(|(_, item)| item) }
+
+    /// Returns references to the smallest and greatest items in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn min_max(&self) -> Option<(&T, &T)> {
+        self.heap.min_max().map(|((_, min), (_, max))| (min, max))
+    }
+
+    /// Returns a reference to the key of the smallest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn min_key(&self) -> Option<&K> { self.heap.min().map(|(key, _)| key) }
+
+    /// Returns a reference to the key of the greatest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn max_key(&self) -> Option<&K> { self.heap.max().map(|(key, _)| key) }
+
+    /// Pushes an item onto the heap, extracting its key.
+    pub fn push(&mut self, item: T) {
+        let key = (self.key)(&item);
+        self.heap.push((key, item));
+    }
+
+    /// Removes the smallest item from the heap and returns it.
+    ///
+    /// Returns `None` if the heap was empty.
+    pub fn pop_min(&mut self) -> Option<T> { self.heap.pop_min().map(|(_, item)| item) }
+
+    /// Removes the greatest item from the heap and returns it.
+    ///
+    /// Returns `None` if the heap was empty.
+    pub fn pop_max(&mut self) -> Option<T> { self.heap.pop_max().map(|(_, item)| item) }
+
+    /// Returns the number of items in the heap.
+    pub fn len(&self) -> usize { self.heap.len() }
+
+    /// Returns `true` if the heap contains no items.
+    pub fn is_empty(&self) -> bool { self.heap.is_empty() }
+
+    /// Removes all items from the heap.
+    pub fn clear(&mut self) { self.heap.clear(); }
+
+    /// Consumes the heap and returns its items as a vector in arbitrary order.
+    pub fn into_vec(self) -> Vec<T> {
+        self.heap.into_vec().into_iter().map(|(_, item)| item).collect()
+    }
+
+    /// Consumes the heap and returns its items as a vector in sorted (ascending) order.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        self.heap.into_sorted_vec().into_iter().map(|(_, item)| item).collect()
+    }
+}
+
+impl<T: Debug, K: Ord, F> Debug for CachedKeyIntervalHeap<T, K, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.heap.data.iter().map(|(_, item)| item)).finish()
+    }
+}
+
+impl<T, K: Ord, F: Fn(&T) -> K> Extend<T> for CachedKeyIntervalHeap<T, K, F> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        let (lower, _) = iter.size_hint();
+        self.heap.reserve(lower);
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+impl<'a, T, K: Ord, F: Fn(&T) -> K> IntoIterator for &'a CachedKeyIntervalHeap<T, K, F> {
+    type Item = &'a T;
+    type IntoIter = CachedKeyIter<'a, K, T>;
+    fn into_iter(self) -> CachedKeyIter<'a, K, T> { self.iter() }
+}
+
+/// An iterator over a `CachedKeyIntervalHeap` in arbitrary order.
+///
+/// Acquire through [`CachedKeyIntervalHeap::iter`](
+/// struct.CachedKeyIntervalHeap.html#method.iter).
+pub struct CachedKeyIter<'a, K: 'a, T: 'a>(slice::Iter<'a, (K, T)>);
+
+impl<'a, K, T> Clone for CachedKeyIter<'a, K, T> {
+    fn clone(&self) -> CachedKeyIter<'a, K, T> { CachedKeyIter(self.0.clone()) }
+}
+
+impl<'a, K, T> Iterator for CachedKeyIter<'a, K, T> {
+    type Item = &'a T;
+    #[inline] fn next(&mut self) -> Option<&'a T> { self.0.next().map(|(_, item)| item) }
+    #[inline] fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
+}
+
+impl<'a, K, T> DoubleEndedIterator for CachedKeyIter<'a, K, T> {
+    fn next_back(&mut self) -> Option<&'a T> { self.0.next_back().map(|(_, item)| item) }
+}
+
+impl<'a, K, T> ExactSizeIterator for CachedKeyIter<'a, K, T> {}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use rand::{thread_rng, Rng};
+    use super::super::IntervalHeap;
+
+    #[test]
+    fn fuzz_cached_key() {
+        let mut rng = thread_rng();
+        for len in 0..50 {
+            let calls = Cell::new(0);
+            let mut heap = IntervalHeap::with_cached_key(|&(k, _): &(u32, u32)| {
+                calls.set(calls.get() + 1);
+                k
+            });
+            let mut all = vec![];
+            for i in 0..len {
+                let item = (rng.gen_range(0, 20), i);
+                heap.push(item);
+                all.push(item);
+                assert!(heap.heap.is_valid());
+            }
+            assert_eq!(calls.get(), len as usize);
+            assert_eq!(heap.min_key(), all.iter().map(|&(k, _)| k).min().as_ref());
+            assert_eq!(heap.max_key(), all.iter().map(|&(k, _)| k).max().as_ref());
+            let mut keys = vec![];
+            while let Some((k, i)) = heap.pop_min() {
+                assert_eq!(all.iter().find(|&&(_, j)| i == j), Some(&(k, i)));
+                keys.push(k);
+            }
+            assert!(keys.windows(2).all(|w| w[0] <= w[1]));
+            assert_eq!(keys.len(), all.len());
+            assert_eq!(calls.get(), len as usize);
+        }
+    }
+}
@@ -20,14 +20,43 @@
 //! [bh]: https://doc.rust-lang.org/stable/std/collections/struct.BinaryHeap.html
 
 extern crate compare;
+extern crate hashbrown;
 #[cfg(test)] extern crate rand;
+#[cfg(feature = "mmap")] extern crate memmap2;
+#[cfg(feature = "serde")] extern crate serde;
 
+use std::cmp::{self, Ordering};
 use std::fmt::{self, Debug};
 use std::iter;
+use std::marker::PhantomData;
+use std::mem::{self, ManuallyDrop};
+use std::ops::{Bound, Deref, DerefMut, RangeBounds};
+use std::ptr;
 use std::slice;
 use std::vec;
 
-use compare::{Compare, Natural, natural};
+use compare::{Compare, Extract, Natural, natural};
+
+pub use addressable::{AddressableIntervalHeap, Handle};
+pub use bounded::{BoundedIntervalHeap, EvictionPolicy};
+pub use durable::DurableIntervalHeap;
+pub use keyed::{CachedKeyIntervalHeap, CachedKeyIter};
+#[cfg(feature = "mmap")] pub use mmap::MmapIntervalHeap;
+pub use priority::{IntervalPriorityQueue, PriorityIter};
+pub use snapshot::Pod;
+pub use stable::{FifoEnd, StableIntervalHeap, StableIter};
+pub use storage::HeapStorage;
+
+mod addressable;
+mod bounded;
+mod durable;
+mod keyed;
+#[cfg(feature = "mmap")] mod mmap;
+mod priority;
+#[cfg(feature = "serde")] mod serde_impl;
+mod snapshot;
+mod stable;
+mod storage;
 
 // An interval heap is a binary tree structure with the following properties:
 //
@@ -40,8 +69,9 @@ use compare::{Compare, Natural, natural};
 // This implies that the min and max items are always in the root node.
 //
 // This interval heap implementation stores its nodes in a linear array
-// using a Vec. Here's an example of the layout of a tree with 13 items
-// (7 nodes) where the numbers represent the *offsets* in the array:
+// using a HeapStorage, which is a Vec by default. Here's an example of the
+// layout of a tree with 13 items (7 nodes) where the numbers represent the
+// *offsets* in the array:
 //
 //          (0 1)
 //         /     \
@@ -53,9 +83,6 @@ use compare::{Compare, Natural, natural};
 // are used for the "right" item of a node. Note: the last node may not
 // have a "right" item.
 
-// FIXME: There may be a better algorithm for turning a vector into an
-// interval heap. Right now, this takes O(n log n) time, I think.
-
 fn is_root(x: usize) -> bool { x < 2 }
 
 /// Set LSB to zero for the "left" item index of a node.
@@ -67,54 +94,234 @@ fn parent_left(x: usize) -> usize {
     left((x - 2) / 2)
 }
 
+/// Receives a notification for every pair of items swapped by the routines
+/// below, so that data kept alongside the items (such as the positions of
+/// addressable items) can follow them around.
+trait Observer {
+    fn swapped(&mut self, a: usize, b: usize);
+}
+
+impl Observer for () {
+    #[inline]
+    fn swapped(&mut self, _: usize, _: usize) {}
+}
+
+#[inline]
+fn swap<T, O: Observer>(v: &mut [T], a: usize, b: usize, obs: &mut O) {
+    v.swap(a, b);
+    obs.swapped(a, b);
+}
+
+/// A slice with a hole: the item at index `pos` has been moved out into `elt`.
+///
+/// Moving the hole around the slice moves the items it passes by one position
+/// each, and dropping the hole fills it with `elt` again. Since dropping also
+/// happens during unwinding, the slice stays a permutation of its original
+/// items even if a comparator panics halfway through a sift.
+///
+/// The observer is told about every move as if the hole's element was
+/// swapped with the moved item.
+///
+/// Moving an item into the hole copies it once instead of swapping it, which
+/// makes sifting large items cheaper. For items of a few words, sifting costs
+/// about the same either way (see `benches/sift.rs`).
+struct Hole<'a, T: 'a, O: 'a + Observer> {
+    data: &'a mut [T],
+    elt: ManuallyDrop<T>,
+    pos: usize,
+    obs: &'a mut O,
+}
+
+impl<'a, T, O: Observer> Hole<'a, T, O> {
+    /// Creates a new hole at index `pos`.
+    fn new(data: &'a mut [T], pos: usize, obs: &'a mut O) -> Hole<'a, T, O> {
+        // SAFETY: The item is only duplicated until the hole is dropped, and
+        // the duplicate in the slice is never accessed in the meantime.
+        let elt = unsafe { ptr::read(&data[pos]) };
+        Hole { data, elt: ManuallyDrop::new(elt), pos, obs }
+    }
+
+    #[inline]
+    fn len(&self) -> usize { self.data.len() }
+
+    #[inline]
+    fn pos(&self) -> usize { self.pos }
+
+    /// Returns a reference to the element that was moved out.
+    #[inline]
+    fn element(&self) -> &T { &self.elt }
+
+    /// Returns a reference to the item at `index`, which must not be the hole.
+    #[inline]
+    fn get(&self, index: usize) -> &T {
+        debug_assert!(index != self.pos);
+        &self.data[index]
+    }
+
+    /// Moves the item at `index` into the hole, leaving the hole at `index`.
+    #[inline]
+    fn move_to(&mut self, index: usize) {
+        debug_assert!(index != self.pos);
+        let src: *const T = &self.data[index];
+        // SAFETY: `src` is in bounds and distinct from the hole, which is in
+        // bounds, too. Afterwards, the item at `index` is the duplicate.
+        unsafe { ptr::copy_nonoverlapping(src, self.data.as_mut_ptr().add(self.pos), 1); }
+        self.obs.swapped(self.pos, index);
+        self.pos = index;
+    }
+
+    /// Exchanges the element with the item at `index`, which must not be the hole.
+    #[inline]
+    fn swap_element(&mut self, index: usize) {
+        debug_assert!(index != self.pos);
+        mem::swap(&mut *self.elt, &mut self.data[index]);
+        self.obs.swapped(self.pos, index);
+    }
+}
+
+impl<'a, T, O: Observer> Drop for Hole<'a, T, O> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: Fill the hole again, ending the duplication.
+        unsafe { ptr::copy_nonoverlapping(&*self.elt, self.data.as_mut_ptr().add(self.pos), 1); }
+    }
+}
+
 /// The first `v.len() - 1` items are considered a valid interval heap
 /// and the last item is to be inserted.
-fn interval_heap_push<T, C: Compare<T>>(v: &mut [T], cmp: &C) {
-    debug_assert!(v.len() > 0);
-    // Start with the last new/modified node and work our way to
-    // the root if necessary...
-    let mut node_max = v.len() - 1;
-    let mut node_min = left(node_max);
-    // The reason for using two variables instead of one is to
-    // get around the special case of the last node only containing
-    // one item (node_min == node_max).
-    if cmp.compares_gt(&v[node_min], &v[node_max]) { v.swap(node_min, node_max); }
-    while !is_root(node_min) {
-        let par_min = parent_left(node_min);
+fn interval_heap_push<T, C: Compare<T>, O: Observer>(v: &mut [T], cmp: &C, obs: &mut O) {
+    debug_assert!(!v.is_empty());
+    let last = v.len() - 1;
+    sift_up(v, cmp, last, obs);
+}
+
+/// The item at index `i` of an otherwise valid interval heap may be out of
+/// place with respect to its node's other item or its ancestors, but not its
+/// descendants. This function moves it up the tree as far as necessary.
+fn sift_up<T, C: Compare<T>, O: Observer>(v: &mut [T], cmp: &C, i: usize, obs: &mut O) {
+    sift_up_hole(&mut Hole::new(v, i, obs), cmp);
+}
+
+/// Like `sift_up`, but for the element of an existing hole.
+fn sift_up_hole<T, C: Compare<T>, O: Observer>(hole: &mut Hole<T, O>, cmp: &C) {
+    // Put the item on the correct side of its node first. The last node may
+    // only contain this one item, which is then both the node's min and max.
+    let i = hole.pos();
+    let other = i ^ 1;
+    if other < hole.len() {
+        let crossed = if i == left(i) { cmp.compares_gt(hole.element(), hole.get(other)) }
+                      else { cmp.compares_lt(hole.element(), hole.get(other)) };
+        if crossed { hole.move_to(other); }
+    }
+    // ...and work our way to the root if necessary.
+    while !is_root(hole.pos()) {
+        let pos = hole.pos();
+        let par_min = parent_left(pos);
         let par_max = par_min + 1;
-        if cmp.compares_lt(&v[node_min], &v[par_min]) {
-            v.swap(par_min, node_min);
-        } else if cmp.compares_lt(&v[par_max], &v[node_max]) {
-            v.swap(par_max, node_max);
+        let is_min = pos == left(pos);
+        let is_max = !is_min || pos + 1 == hole.len();
+        if is_min && cmp.compares_lt(hole.element(), hole.get(par_min)) {
+            hole.move_to(par_min);
+        } else if is_max && cmp.compares_gt(hole.element(), hole.get(par_max)) {
+            hole.move_to(par_max);
         } else {
             return; // nothing to do anymore
         }
-        debug_assert!(cmp.compares_le(&v[node_min], &v[node_max]));
-        node_min = par_min;
-        node_max = par_max;
     }
 }
 
-/// The min item in the root node of an otherwise valid interval heap
-/// has been been replaced with some other value without violating rule (1)
-/// for the root node. This function restores the interval heap properties.
-fn update_min<T, C: Compare<T>>(v: &mut [T], cmp: &C) {
-    // Starting at the root, we go down the tree...
-    debug_assert!(cmp.compares_le(&v[0], &v[1]));
-    let mut left = 0;
+/// The item at index `i` of an otherwise valid interval heap has been
+/// replaced with some other value. This function restores the interval heap
+/// properties, moving the item up or down the tree as necessary.
+fn update<T, C: Compare<T>, O: Observer>(v: &mut [T], cmp: &C, i: usize, obs: &mut O) {
+    // Only the new item can be out of place with respect to the ancestors.
+    // If it moves up, the node receives an item from its parent, which is in
+    // order with respect to the node's descendants.
+    sift_up(v, cmp, i, obs);
+    let l = left(i);
+    sift_down_min(v, cmp, l, obs);
+    if l + 1 < v.len() { sift_down_max(v, cmp, l + 1, obs); }
+}
+
+/// Removes the item at index `i` of a valid interval heap by swapping it
+/// with the last item and restoring the interval heap properties afterwards.
+///
+/// The observer sees the item being swapped to the end, so its own data for
+/// the removed item is left at index `storage.len()` once this returns.
+fn interval_heap_remove<T, C, S, O>(storage: &mut S, cmp: &C, i: usize, obs: &mut O) -> T
+        where C: Compare<T>, S: HeapStorage<T>, O: Observer {
+    let last = storage.len() - 1;
+    swap(storage.as_mut_slice(), i, last, obs);
+    let item = storage.pop().unwrap();
+    let v = storage.as_mut_slice();
+    // Removing from the root only affects one side of the heap, and the
+    // last item is always in order with the other root item.
+    match i {
+        _ if i >= v.len() => {}
+        0 if v.len() > 1 => update_min(v, cmp, obs),
+        1 if v.len() > 2 => update_max(v, cmp, obs),
+        0 | 1 => {}
+        _ => update(v, cmp, i, obs),
+    }
+    item
+}
+
+/// Returns `true` if appending `len2` items to an interval heap of `len1 >= len2`
+/// items is expected to be cheaper by rebuilding the whole heap than by
+/// pushing the items one by one.
+///
+/// Rebuilding takes about `2 * (len1 + len2)` comparisons in the worst case,
+/// while pushing takes about `len2 * log2(len1)` comparisons in the worst
+/// case.
+fn better_to_rebuild(len1: usize, len2: usize) -> bool {
+    let log2 = (usize::BITS - len1.leading_zeros()) as usize;
+    2 * (len1 + len2) < len2 * log2
+}
+
+/// Turns an arbitrary slice into a valid interval heap in `O(n)` time.
+///
+/// This works bottom-up: after putting every node's pair of items into order,
+/// each internal node is sifted down (first its min, then its max) starting
+/// with the last internal node and working our way back to the root. By the
+/// time a node is processed, both of its subtrees are already valid interval
+/// heaps.
+fn heapify<T, C: Compare<T>>(v: &mut [T], cmp: &C) {
+    if v.len() < 2 { return; }
+    for l in (0..v.len() - 1).step_by(2) {
+        if cmp.compares_gt(&v[l], &v[l + 1]) { v.swap(l, l + 1); }
+    }
+    let last = left(v.len() - 1);
+    if is_root(last) { return; }
+    // Internal nodes are never the last node, so they always have both items.
+    let mut node = parent_left(last) + 2;
+    while node > 0 {
+        node -= 2;
+        sift_down_min(v, cmp, node, &mut ());
+        sift_down_max(v, cmp, node + 1, &mut ());
+    }
+}
+
+/// The min item of the node at `left` has been replaced with some other
+/// value without violating rule (1) for that node, and the node's subtrees
+/// are valid interval heaps. This function moves the item down the tree
+/// until the subtree rooted at `left` satisfies the min-side properties.
+fn sift_down_min<T, C: Compare<T>, O: Observer>(v: &mut [T], cmp: &C, left: usize,
+                                                 obs: &mut O) {
+    let mut hole = Hole::new(v, left, obs);
     loop {
-        let c1 = left * 2 + 2; // index of 1st child's left item
-        let c2 = left * 2 + 4; // index of 2nd child's left item
-        if v.len() <= c1 { return; } // No children. We're done.
+        let c1 = hole.pos() * 2 + 2; // index of 1st child's left item
+        let c2 = hole.pos() * 2 + 4; // index of 2nd child's left item
+        if hole.len() <= c1 { return; } // No children. We're done.
         // Pick child with lowest min
-        let ch = if v.len() <= c2 || cmp.compares_lt(&v[c1], &v[c2]) { c1 }
+        let ch = if hole.len() <= c2 || cmp.compares_lt(hole.get(c1), hole.get(c2)) { c1 }
                  else { c2 };
-        if cmp.compares_lt(&v[ch], &v[left]) {
-            v.swap(ch, left);
-            left = ch;
-            let right = left + 1;
-            if right < v.len() {
-                if cmp.compares_gt(&v[left], &v[right]) { v.swap(left, right); }
+        if cmp.compares_lt(hole.get(ch), hole.element()) {
+            hole.move_to(ch);
+            let right = ch + 1;
+            // If the item crossed the child's max, it stays there as the max
+            // and the child's old max continues down the tree instead.
+            if right < hole.len() && cmp.compares_gt(hole.element(), hole.get(right)) {
+                hole.swap_element(right);
             }
         } else {
             break;
@@ -122,28 +329,160 @@ fn update_min<T, C: Compare<T>>(v: &mut [T], cmp: &C) {
     }
 }
 
+/// The max item of the node at `right` has been replaced with some other
+/// value without violating rule (1) for that node, and the node's subtrees
+/// are valid interval heaps. This function moves the item down the tree
+/// until the subtree rooted at `right` satisfies the max-side properties.
+fn sift_down_max<T, C: Compare<T>, O: Observer>(v: &mut [T], cmp: &C, right: usize,
+                                                 obs: &mut O) {
+    let mut hole = Hole::new(v, right, obs);
+    loop {
+        let c1 = hole.pos() * 2; // index of 1st child's left item
+        let c2 = hole.pos() * 2 + 2; // index of 2nd child's left item
+        if hole.len() <= c1 { return; } // No children. We're done.
+        // The max of a child is its right item, unless the child is the
+        // last node and only holds a single item.
+        let m1 = cmp::min(c1 + 1, hole.len() - 1);
+        // Pick child with greatest max
+        let ch = if hole.len() <= c2 { m1 } else {
+            let m2 = cmp::min(c2 + 1, hole.len() - 1);
+            if cmp.compares_gt(hole.get(m1), hole.get(m2)) { m1 } else { m2 }
+        };
+        if cmp.compares_gt(hole.get(ch), hole.element()) {
+            hole.move_to(ch);
+            if ch == left(ch) { return; } // Single-item leaf. We're done.
+            // If the item crossed the child's min, it stays there as the min
+            // and the child's old min continues down the tree instead.
+            let left = ch - 1;
+            if cmp.compares_lt(hole.element(), hole.get(left)) { hole.swap_element(left); }
+        } else {
+            break;
+        }
+    }
+}
+
+/// The min item in the root node of an otherwise valid interval heap
+/// has been been replaced with some other value without violating rule (1)
+/// for the root node. This function restores the interval heap properties.
+fn update_min<T, C: Compare<T>, O: Observer>(v: &mut [T], cmp: &C, obs: &mut O) {
+    debug_assert!(cmp.compares_le(&v[0], &v[1]));
+    // Starting at the root, we go down the tree...
+    sift_down_min(v, cmp, 0, obs);
+}
+
 /// The max item in the root node of an otherwise valid interval heap
 /// has been been replaced with some other value without violating rule (1)
 /// for the root node. This function restores the interval heap properties.
-fn update_max<T, C: Compare<T>>(v: &mut [T], cmp: &C) {
+fn update_max<T, C: Compare<T>, O: Observer>(v: &mut [T], cmp: &C, obs: &mut O) {
     debug_assert!(cmp.compares_le(&v[0], &v[1]));
     // Starting at the root, we go down the tree...
-    let mut right = 1;
+    sift_down_max(v, cmp, 1, obs);
+}
+
+/// The min item in the root node of an otherwise valid interval heap has
+/// been replaced with an arbitrary value. This function restores the interval
+/// heap properties.
+fn replaced_min<T, C: Compare<T>>(v: &mut [T], cmp: &C) {
+    if v.len() < 2 { return; }
+    // If the new item crossed the max, it becomes the max and the old max has
+    // to find its place on the min side.
+    if cmp.compares_gt(&v[0], &v[1]) { v.swap(0, 1); }
+    update_min(v, cmp, &mut ());
+}
+
+/// The max item in the root node of an otherwise valid interval heap has
+/// been replaced with an arbitrary value. This function restores the interval
+/// heap properties.
+fn replaced_max<T, C: Compare<T>>(v: &mut [T], cmp: &C) {
+    if v.len() < 2 { return; }
+    // If the new item crossed the min, it becomes the min and the old min has
+    // to find its place on the max side.
+    if cmp.compares_gt(&v[0], &v[1]) { v.swap(0, 1); }
+    update_max(v, cmp, &mut ());
+}
+
+/// Like `update_min`, but instead of comparing the new min item with the
+/// smaller child at every level, the hole left by it is first moved all the
+/// way down along the smaller children. The item is then sifted up from the
+/// leaf, which usually is a short way since it came from the bottom of the
+/// heap. This takes about half as many comparisons.
+fn update_min_bottom_up<T, C: Compare<T>, O: Observer>(v: &mut [T], cmp: &C, obs: &mut O) {
+    debug_assert!(cmp.compares_le(&v[0], &v[1]));
+    let mut hole = Hole::new(v, 0, obs);
     loop {
-        let c1 = right * 2 + 1; // index of 1st child's right item
-        let c2 = right * 2 + 3; // index of 2nd child's right item
-        if v.len() <= c1 { return; } // No children. We're done.
-        // Pick child with greatest max
-        let ch = if v.len() <= c2 || cmp.compares_gt(&v[c1], &v[c2]) { c1 }
+        let c1 = hole.pos() * 2 + 2; // index of 1st child's left item
+        let c2 = hole.pos() * 2 + 4; // index of 2nd child's left item
+        if hole.len() <= c1 { break; } // Reached a leaf.
+        // A child's min is never greater than its own max, so moving the
+        // lowest min up keeps rule (1) intact on the way down.
+        let ch = if hole.len() <= c2 || cmp.compares_lt(hole.get(c1), hole.get(c2)) { c1 }
                  else { c2 };
-        if cmp.compares_gt(&v[ch], &v[right]) {
-            v.swap(ch, right);
-            right = ch;
-            let left = right - 1; // always exists
-            if cmp.compares_gt(&v[left], &v[right]) { v.swap(left, right); }
-        } else {
-            break;
-        }
+        hole.move_to(ch);
+    }
+    sift_up_hole(&mut hole, cmp);
+}
+
+/// Like `update_max`, but moves the hole down along the greater children
+/// first and sifts the new max item up from the leaf afterwards. See
+/// `update_min_bottom_up`.
+fn update_max_bottom_up<T, C: Compare<T>, O: Observer>(v: &mut [T], cmp: &C, obs: &mut O) {
+    debug_assert!(cmp.compares_le(&v[0], &v[1]));
+    let mut hole = Hole::new(v, 1, obs);
+    loop {
+        let c1 = hole.pos() * 2; // index of 1st child's left item
+        let c2 = hole.pos() * 2 + 2; // index of 2nd child's left item
+        if hole.len() <= c1 { break; } // Reached a leaf.
+        // The max of a child is its right item, unless the child is the
+        // last node and only holds a single item.
+        let m1 = cmp::min(c1 + 1, hole.len() - 1);
+        let ch = if hole.len() <= c2 { m1 } else {
+            let m2 = cmp::min(c2 + 1, hole.len() - 1);
+            if cmp.compares_gt(hole.get(m1), hole.get(m2)) { m1 } else { m2 }
+        };
+        hole.move_to(ch);
+        if ch == left(ch) { break; } // Single-item leaf.
+    }
+    sift_up_hole(&mut hole, cmp);
+}
+
+/// Compares indices into a slice by the items they refer to.
+struct ByIndex<'a, T: 'a, C: 'a> {
+    items: &'a [T],
+    cmp: &'a C,
+}
+
+impl<'a, T, C: Compare<T>> Compare<usize> for ByIndex<'a, T, C> {
+    fn compare(&self, a: &usize, b: &usize) -> Ordering {
+        self.cmp.compare(&self.items[*a], &self.items[*b])
+    }
+}
+
+/// A comparator that compares items with a closure.
+///
+/// This makes ad-hoc orderings usable without naming a `Compare` type. See
+/// [`IntervalHeap::with_cmp_fn`](struct.IntervalHeap.html#method.with_cmp_fn).
+///
+/// # Examples
+///
+/// ```
+/// use interval_heap::{FnComparator, IntervalHeap};
+///
+/// let cmp = FnComparator(|a: &(u32, char), b: &(u32, char)| a.1.cmp(&b.1));
+/// let mut heap = IntervalHeap::with_comparator(cmp);
+/// heap.push((1, 'b'));
+/// heap.push((2, 'a'));
+/// assert_eq!(heap.min(), Some(&(2, 'a')));
+/// ```
+#[derive(Clone, Copy)]
+pub struct FnComparator<F>(pub F);
+
+impl<T, F: Fn(&T, &T) -> Ordering> Compare<T> for FnComparator<F> {
+    fn compare(&self, a: &T, b: &T) -> Ordering { (self.0)(a, b) }
+}
+
+impl<F> Debug for FnComparator<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("FnComparator")
     }
 }
 
@@ -153,16 +492,61 @@ fn update_max<T, C: Compare<T>>(v: &mut [T], cmp: &C) {
 /// item's ordering relative to any other item, as determined by the heap's
 /// comparator, changes while it is in the heap. This is normally only
 /// possible through `Cell`, `RefCell`, global state, I/O, or unsafe code.
+///
+/// If the comparator panics, the heap still owns exactly the items it held
+/// before the failed operation (minus an item that was being popped, or plus
+/// an item that was being pushed), but they may no longer be in valid heap
+/// order. Such a heap should only be dropped, or its items recovered through
+/// [`into_vec`](#method.into_vec) and used to build a new heap.
+///
+/// The items are stored in a [`HeapStorage`](trait.HeapStorage.html), which is a `Vec` unless
+/// another storage is given through [`from_storage`](#method.from_storage). Operations that
+/// need to allocate or hand out the `Vec` itself, such as [`into_vec`](#method.into_vec), are
+/// only available on `Vec`-backed heaps.
 #[derive(Clone)]
-pub struct IntervalHeap<T, C: Compare<T> = Natural<T>> {
-    data: Vec<T>,
+pub struct IntervalHeap<T, C: Compare<T> = Natural<T>, S: HeapStorage<T> = Vec<T>> {
+    data: S,
     cmp: C,
+    pop_strategy: PopStrategy,
+    marker: PhantomData<T>,
+}
+
+/// Determines how an `IntervalHeap` restores its invariants after popping its smallest or
+/// greatest item.
+///
+/// Both strategies move the heap's last item into the popped item's place and find its new
+/// place from there, so they only differ in the number of comparisons they make.
+///
+/// # Examples
+///
+/// ```
+/// use interval_heap::{IntervalHeap, PopStrategy};
+///
+/// let names = vec!["pear", "apple", "plum", "fig"];
+/// let mut heap = IntervalHeap::from_vec_with_pop_strategy(names, PopStrategy::BottomUp);
+/// assert_eq!(heap.pop_strategy(), PopStrategy::BottomUp);
+/// assert_eq!(heap.pop_min(), Some("apple"));
+/// assert_eq!(heap.pop_max(), Some("plum"));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum PopStrategy {
+    /// Sifts the last item down from the root, comparing it with the smaller (or greater)
+    /// child at every level. This is the default.
+    #[default]
+    TopDown,
+    /// Moves the popped item's place all the way down to a leaf along the smaller (or
+    /// greater) children before sifting the last item up from there.
+    ///
+    /// Since the last item usually belongs near the bottom of the heap, this takes about half
+    /// as many comparisons as `TopDown`, which pays off for expensive comparators such as
+    /// those comparing strings or tuples. Cheap comparators are often faster with `TopDown`.
+    BottomUp,
 }
 
-impl<T, C: Compare<T> + Default> Default for IntervalHeap<T, C> {
+impl<T, C: Compare<T> + Default, S: HeapStorage<T> + Default> Default for IntervalHeap<T, C, S> {
     #[inline]
-    fn default() -> IntervalHeap<T, C> {
-        Self::with_comparator(C::default())
+    fn default() -> IntervalHeap<T, C, S> {
+        Self::from_storage_and_comparator(S::default(), C::default())
     }
 }
 
@@ -196,6 +580,45 @@ impl<T: Ord> IntervalHeap<T> {
     pub fn with_capacity(capacity: usize) -> IntervalHeap<T> {
         Self::with_capacity_and_comparator(capacity, natural())
     }
+
+    /// Returns an empty heap ordered according to the natural order of its items, which
+    /// uses the given strategy to restore its invariants after popping an item.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::{IntervalHeap, PopStrategy};
+    ///
+    /// let mut heap = IntervalHeap::with_pop_strategy(PopStrategy::BottomUp);
+    /// heap.extend(vec![(2, "b"), (1, "a"), (3, "c")]);
+    /// assert_eq!(heap.pop_min(), Some((1, "a")));
+    /// assert_eq!(heap.pop_max(), Some((3, "c")));
+    /// ```
+    pub fn with_pop_strategy(strategy: PopStrategy) -> IntervalHeap<T> {
+        Self::with_comparator_and_pop_strategy(natural(), strategy)
+    }
+
+    /// Returns a heap containing all the items of the given vector and ordered
+    /// according to the natural order of its items, which uses the given strategy to
+    /// restore its invariants after popping an item.
+    ///
+    /// See [`PopStrategy`](enum.PopStrategy.html) for an example.
+    pub fn from_vec_with_pop_strategy(vec: Vec<T>, strategy: PopStrategy) -> IntervalHeap<T> {
+        let mut heap = Self::from_vec_and_comparator(vec, natural());
+        heap.pop_strategy = strategy;
+        heap
+    }
+}
+
+impl<T: Ord, S: HeapStorage<T>> IntervalHeap<T, Natural<T>, S> {
+    /// Returns a heap backed by the given storage, containing its items and ordered
+    /// according to their natural order.
+    ///
+    /// The storage's items are rearranged into a valid heap in `O(n)` time. See
+    /// [`HeapStorage`](trait.HeapStorage.html) for an example.
+    pub fn from_storage(storage: S) -> IntervalHeap<T, Natural<T>, S> {
+        Self::from_storage_and_comparator(storage, natural())
+    }
 }
 
 impl<T: Ord> From<Vec<T>> for IntervalHeap<T> {
@@ -216,73 +639,116 @@ impl<T: Ord> From<Vec<T>> for IntervalHeap<T> {
     }
 }
 
-impl<T, C: Compare<T>> IntervalHeap<T, C> {
-    /// Returns an empty heap ordered according to the given comparator.
-    pub fn with_comparator(cmp: C) -> IntervalHeap<T, C> {
-        IntervalHeap { data: vec![], cmp: cmp }
-    }
-
-    /// Returns an empty heap with the given capacity and ordered according to the given
-    /// comparator.
-    pub fn with_capacity_and_comparator(capacity: usize, cmp: C) -> IntervalHeap<T, C> {
-        IntervalHeap { data: Vec::with_capacity(capacity), cmp: cmp }
+impl<T, F: Fn(&T, &T) -> Ordering> IntervalHeap<T, FnComparator<F>> {
+    /// Returns an empty heap ordered according to the given comparison function.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut heap = IntervalHeap::with_cmp_fn(|a: &f64, b: &f64| a.total_cmp(b));
+    /// heap.push(2.5);
+    /// heap.push(-1.0);
+    /// heap.push(7.25);
+    /// assert_eq!(heap.min_max(), Some((&-1.0, &7.25)));
+    /// ```
+    pub fn with_cmp_fn(f: F) -> IntervalHeap<T, FnComparator<F>> {
+        Self::with_comparator(FnComparator(f))
     }
 
     /// Returns a heap containing all the items of the given vector and ordered
-    /// according to the given comparator.
-    pub fn from_vec_and_comparator(mut vec: Vec<T>, cmp: C) -> IntervalHeap<T, C> {
-        for to in 2 .. vec.len() + 1 {
-            interval_heap_push(&mut vec[..to], &cmp);
-        }
-        let heap = IntervalHeap { data: vec, cmp: cmp };
-        debug_assert!(heap.is_valid());
-        heap
+    /// according to the given comparison function.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let heap = IntervalHeap::from_vec_by(vec!["ccc", "a", "bb"], |a, b| a.len().cmp(&b.len()));
+    /// assert_eq!(heap.min_max(), Some((&"a", &"ccc")));
+    /// ```
+    pub fn from_vec_by(vec: Vec<T>, f: F) -> IntervalHeap<T, FnComparator<F>> {
+        Self::from_vec_and_comparator(vec, FnComparator(f))
     }
 
-    /// Returns an iterator visiting all items in the heap in arbitrary order.
-    pub fn iter(&self) -> Iter<T> {
-        debug_assert!(self.is_valid());
-        Iter(self.data.iter())
+    /// Returns a heap containing all the items of the given iterator and ordered
+    /// according to the given comparison function.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let heap = IntervalHeap::from_iter_by(1..10, |a: &i32, b: &i32| b.cmp(a));
+    /// assert_eq!(heap.into_sorted_vec(), [9, 8, 7, 6, 5, 4, 3, 2, 1]);
+    /// ```
+    pub fn from_iter_by<I: IntoIterator<Item = T>>(iter: I, f: F)
+                                                   -> IntervalHeap<T, FnComparator<F>> {
+        Self::from_vec_by(iter.into_iter().collect(), f)
     }
+}
 
-    /// Returns a reference to the smallest item in the heap.
+impl<T, K: Ord, F: Fn(&T) -> K> IntervalHeap<T, Extract<F, Natural<K>>> {
+    /// Returns an empty heap ordered according to the natural order of the keys `key`
+    /// extracts from its items.
     ///
-    /// Returns `None` if the heap is empty.
-    pub fn min(&self) -> Option<&T> {
-        debug_assert!(self.is_valid());
-        match self.data.len() {
-            0 => None,
-            _ => Some(&self.data[0]),
-        }
+    /// The key is extracted anew for every comparison. If that is expensive, consider
+    /// [`with_cached_key`](#method.with_cached_key) instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// struct Job { name: &'static str, deadline: u32 }
+    ///
+    /// let mut jobs = IntervalHeap::with_key(|job: &Job| job.deadline);
+    /// jobs.push(Job { name: "taxes", deadline: 15 });
+    /// jobs.push(Job { name: "laundry", deadline: 3 });
+    /// jobs.push(Job { name: "report", deadline: 7 });
+    /// assert_eq!(jobs.pop_min().map(|job| job.name), Some("laundry"));
+    /// assert_eq!(jobs.pop_max().map(|job| job.name), Some("taxes"));
+    /// ```
+    pub fn with_key(key: F) -> IntervalHeap<T, Extract<F, Natural<K>>> {
+        Self::with_comparator(Extract::new(key))
     }
 
-    /// Returns a reference to the greatest item in the heap.
+    /// Returns an empty heap ordered according to the natural order of the keys `key`
+    /// extracts from its items, extracting each item's key only once when it is pushed.
     ///
-    /// Returns `None` if the heap is empty.
-    pub fn max(&self) -> Option<&T> {
-        debug_assert!(self.is_valid());
-        match self.data.len() {
-            0 => None,
-            1 => Some(&self.data[0]),
-            _ => Some(&self.data[1]),
-        }
+    /// The heap stores the keys alongside the items. See
+    /// [`CachedKeyIntervalHeap`](struct.CachedKeyIntervalHeap.html) for an example.
+    pub fn with_cached_key(key: F) -> CachedKeyIntervalHeap<T, K, F> {
+        CachedKeyIntervalHeap::new(key)
+    }
+}
+
+impl<T, C: Compare<T>> IntervalHeap<T, C> {
+    /// Returns an empty heap ordered according to the given comparator.
+    pub fn with_comparator(cmp: C) -> IntervalHeap<T, C> {
+        Self::from_storage_and_comparator(vec![], cmp)
     }
 
-    /// Returns references to the smallest and greatest items in the heap.
-    ///
-    /// Returns `None` if the heap is empty.
-    pub fn min_max(&self) -> Option<(&T, &T)> {
-        debug_assert!(self.is_valid());
-        match self.data.len() {
-            0 => None,
-            1 => Some((&self.data[0], &self.data[0])),
-            _ => Some((&self.data[0], &self.data[1])),
-        }
+    /// Returns an empty heap with the given capacity and ordered according to the given
+    /// comparator.
+    pub fn with_capacity_and_comparator(capacity: usize, cmp: C) -> IntervalHeap<T, C> {
+        Self::from_storage_and_comparator(Vec::with_capacity(capacity), cmp)
     }
 
-    /// Returns the number of items the heap can hold without reallocation.
-    pub fn capacity(&self) -> usize {
-        self.data.capacity()
+    /// Returns an empty heap ordered according to the given comparator, which uses the
+    /// given strategy to restore its invariants after popping an item.
+    pub fn with_comparator_and_pop_strategy(cmp: C, strategy: PopStrategy)
+                                            -> IntervalHeap<T, C> {
+        let mut heap = Self::with_comparator(cmp);
+        heap.pop_strategy = strategy;
+        heap
+    }
+
+    /// Returns a heap containing all the items of the given vector and ordered
+    /// according to the given comparator.
+    pub fn from_vec_and_comparator(vec: Vec<T>, cmp: C) -> IntervalHeap<T, C> {
+        Self::from_storage_and_comparator(vec, cmp)
     }
 
     /// Reserves the minimum capacity for exactly `additional` more items to be inserted into the
@@ -309,48 +775,104 @@ impl<T, C: Compare<T>> IntervalHeap<T, C> {
         self.data.shrink_to_fit()
     }
 
-    /// Removes the smallest item from the heap and returns it.
+    /// Retains only the items specified by the predicate.
     ///
-    /// Returns `None` if the heap was empty.
-    pub fn pop_min(&mut self) -> Option<T> {
+    /// In other words, removes all items `e` for which `f(&e)` returns `false`. The predicate
+    /// is called exactly once for each item, in arbitrary order. If any items are removed,
+    /// the remaining ones are rebuilt into a valid heap in `O(n)` time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut heap = IntervalHeap::from(vec![1, 2, 3, 4, 5, 6]);
+    /// heap.retain(|&x| x % 3 != 0);
+    /// assert_eq!(heap.into_sorted_vec(), [1, 2, 4, 5]);
+    /// ```
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, f: F) {
         debug_assert!(self.is_valid());
-        let min = match self.data.len() {
-            0 => None,
-            1...2 => Some(self.data.swap_remove(0)),
-            _ => {
-                let res = self.data.swap_remove(0);
-                update_min(&mut self.data, &self.cmp);
-                Some(res)
-            }
-        };
+        let len = self.data.len();
+        self.data.retain(f);
+        if self.data.len() < len {
+            heapify(&mut self.data, &self.cmp);
+        }
         debug_assert!(self.is_valid());
-        min
     }
 
-    /// Removes the greatest item from the heap and returns it.
+    /// Retains only the items specified by the predicate, passing a mutable reference to it.
     ///
-    /// Returns `None` if the heap was empty.
-    pub fn pop_max(&mut self) -> Option<T> {
+    /// In other words, removes all items `e` for which `f(&mut e)` returns `false`. The
+    /// predicate is called exactly once for each item, in arbitrary order, and may modify the
+    /// items it retains. Afterwards, the remaining items are rebuilt into a valid heap in
+    /// `O(n)` time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut heap = IntervalHeap::from(vec![1, 2, 3, 4, 5, 6]);
+    /// heap.retain_mut(|x| { *x *= 10; *x != 30 });
+    /// assert_eq!(heap.into_sorted_vec(), [10, 20, 40, 50, 60]);
+    /// ```
+    pub fn retain_mut<F: FnMut(&mut T) -> bool>(&mut self, f: F) {
         debug_assert!(self.is_valid());
-        let max = match self.data.len() {
-            0...2 => self.data.pop(),
-            _ => {
-                let res = self.data.swap_remove(1);
-                update_max(&mut self.data, &self.cmp);
-                Some(res)
-            }
-        };
+        self.data.retain_mut(f);
+        heapify(&mut self.data, &self.cmp);
         debug_assert!(self.is_valid());
-        max
     }
 
-    /// Pushes an item onto the heap.
-    pub fn push(&mut self, item: T) {
-        debug_assert!(self.is_valid());
-        self.data.push(item);
-        interval_heap_push(&mut self.data, &self.cmp);
-        debug_assert!(self.is_valid());
-    }
+    /// Moves all the items of `other` into `self`, leaving `other` empty.
+    ///
+    /// Depending on the sizes of the heaps, this either pushes the items of the smaller heap
+    /// onto the larger one or concatenates both and rebuilds the result in `O(n)` time,
+    /// whichever is expected to be cheaper. It is a logic error for the heaps' comparators
+    /// to order items differently.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut a = IntervalHeap::from(vec![3, 1, 4]);
+    /// let mut b = IntervalHeap::from(vec![1, 5, 9, 2]);
+    /// a.append(&mut b);
+    /// assert!(b.is_empty());
+    /// assert_eq!(a.into_sorted_vec(), [1, 1, 2, 3, 4, 5, 9]);
+    /// ```
+    pub fn append(&mut self, other: &mut Self) {
+        if self.len() < other.len() {
+            mem::swap(&mut self.data, &mut other.data);
+        }
+        if better_to_rebuild(self.len(), other.len()) {
+            self.data.append(&mut other.data);
+            heapify(&mut self.data, &self.cmp);
+            debug_assert!(self.is_valid());
+        } else {
+            self.extend(other.drain());
+        }
+    }
+
+    /// Returns a heap containing all the items of both given heaps, ordered according to the
+    /// comparator of `a`.
+    ///
+    /// See [`append`](#method.append) for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let a = IntervalHeap::from(vec![3, 1, 4]);
+    /// let b = IntervalHeap::from(vec![1, 5, 9, 2]);
+    /// let heap = IntervalHeap::merge(a, b);
+    /// assert_eq!(heap.min_max(), Some((&1, &9)));
+    /// ```
+    pub fn merge(mut a: Self, mut b: Self) -> Self {
+        a.append(&mut b);
+        a
+    }
 
     /// Consumes the heap and returns its items as a vector in arbitrary order.
     pub fn into_vec(self) -> Vec<T> { self.data }
@@ -360,43 +882,674 @@ impl<T, C: Compare<T>> IntervalHeap<T, C> {
         let mut vec = self.data;
         for hsize in (2..vec.len()).rev() {
             vec.swap(1, hsize);
-            update_max(&mut vec[..hsize], &self.cmp);
+            update_max(&mut vec[..hsize], &self.cmp, &mut ());
         }
         vec
     }
 
+    /// Clears the heap, returning an iterator over the removed items in arbitrary order.
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        Drain(self.data.drain(..))
+    }
+}
+
+impl<T, C: Compare<T>, S: HeapStorage<T>> IntervalHeap<T, C, S> {
+    /// Returns a heap backed by the given storage, containing its items and ordered
+    /// according to the given comparator.
+    ///
+    /// The storage's items are rearranged into a valid heap in `O(n)` time.
+    pub fn from_storage_and_comparator(mut storage: S, cmp: C) -> IntervalHeap<T, C, S> {
+        heapify(storage.as_mut_slice(), &cmp);
+        Self::from_heap_storage(storage, cmp)
+    }
+
+    /// Returns a heap backed by the given storage, whose items are already in heap order.
+    pub(crate) fn from_heap_storage(storage: S, cmp: C) -> IntervalHeap<T, C, S> {
+        let heap = IntervalHeap {
+            data: storage,
+            cmp,
+            pop_strategy: PopStrategy::TopDown,
+            marker: PhantomData,
+        };
+        debug_assert!(heap.is_valid());
+        heap
+    }
+
+    /// Consumes the heap and returns its storage, holding the items in arbitrary order.
+    pub fn into_storage(self) -> S { self.data }
+
+    /// Returns the strategy used to restore the heap's invariants after popping an item.
+    pub fn pop_strategy(&self) -> PopStrategy { self.pop_strategy }
+
+    /// Sets the strategy used to restore the heap's invariants after popping an item.
+    ///
+    /// This applies to every operation removing the heap's smallest or greatest item, such as
+    /// [`pop_min`](#method.pop_min) or [`drain_sorted`](#method.drain_sorted). The strategy
+    /// can also be chosen when creating the heap, e.g. with
+    /// [`with_pop_strategy`](#method.with_pop_strategy).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::{IntervalHeap, PopStrategy};
+    ///
+    /// let mut heap = IntervalHeap::from(vec!["pear", "apple", "plum", "fig"]);
+    /// heap.set_pop_strategy(PopStrategy::BottomUp);
+    /// assert_eq!(heap.pop_min(), Some("apple"));
+    /// assert_eq!(heap.pop_max(), Some("plum"));
+    /// ```
+    pub fn set_pop_strategy(&mut self, strategy: PopStrategy) {
+        self.pop_strategy = strategy;
+    }
+
+    /// Returns an iterator visiting all items in the heap in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        debug_assert!(self.is_valid());
+        Iter(self.data.as_slice().iter())
+    }
+
+    /// Returns a reference to the smallest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn min(&self) -> Option<&T> {
+        debug_assert!(self.is_valid());
+        let v = self.data.as_slice();
+        match v.len() {
+            0 => None,
+            _ => Some(&v[0]),
+        }
+    }
+
+    /// Returns a reference to the greatest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn max(&self) -> Option<&T> {
+        debug_assert!(self.is_valid());
+        let v = self.data.as_slice();
+        match v.len() {
+            0 => None,
+            1 => Some(&v[0]),
+            _ => Some(&v[1]),
+        }
+    }
+
+    /// Returns references to the smallest and greatest items in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn min_max(&self) -> Option<(&T, &T)> {
+        debug_assert!(self.is_valid());
+        let v = self.data.as_slice();
+        match v.len() {
+            0 => None,
+            1 => Some((&v[0], &v[0])),
+            _ => Some((&v[0], &v[1])),
+        }
+    }
+
+    /// Returns a mutable reference to the smallest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty. The heap's invariants are restored when the
+    /// returned guard is dropped, even if the item was changed to be greater than the
+    /// heap's greatest item.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut heap = IntervalHeap::from(vec![2, 5, 3]);
+    /// *heap.peek_min_mut().unwrap() = 7;
+    /// assert_eq!(heap.min_max(), Some((&3, &7)));
+    /// ```
+    pub fn peek_min_mut(&mut self) -> Option<PeekMin<'_, T, C, S>> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(PeekMin { heap: self, sift: false })
+        }
+    }
+
+    /// Returns a mutable reference to the greatest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty. The heap's invariants are restored when the
+    /// returned guard is dropped, even if the item was changed to be less than the heap's
+    /// smallest item.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut heap = IntervalHeap::from(vec![2, 5, 3]);
+    /// *heap.peek_max_mut().unwrap() = 1;
+    /// assert_eq!(heap.min_max(), Some((&1, &3)));
+    /// ```
+    pub fn peek_max_mut(&mut self) -> Option<PeekMax<'_, T, C, S>> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(PeekMax { heap: self, sift: false })
+        }
+    }
+
+    /// Returns references to the `k` smallest items in the heap in ascending order, without
+    /// modifying the heap.
+    ///
+    /// Returns all items if the heap holds fewer than `k` of them. This walks the heap's tree
+    /// from the root and takes `O(k log k)` time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let heap = IntervalHeap::from(vec![5, 1, 8, 3, 9, 2]);
+    /// assert_eq!(heap.smallest_k(3), [&1, &2, &3]);
+    /// assert_eq!(heap.len(), 6);
+    /// ```
+    pub fn smallest_k(&self, k: usize) -> Vec<&T> {
+        debug_assert!(self.is_valid());
+        let v = self.data.as_slice();
+        let mut items = Vec::with_capacity(cmp::min(k, v.len()));
+        if k == 0 || v.is_empty() { return items; }
+        // Every item is greater than or equal to the item through which it is
+        // reached: a node's right item is reached through its left item, which
+        // in turn is reached through the left item of the node's parent.
+        let mut frontier = IntervalHeap::with_comparator(ByIndex { items: v, cmp: &self.cmp });
+        frontier.push(0);
+        while items.len() < k {
+            let i = match frontier.pop_min() { Some(i) => i, None => break };
+            items.push(&v[i]);
+            if i == left(i) {
+                for &j in &[i + 1, i * 2 + 2, i * 2 + 4] {
+                    if j < v.len() { frontier.push(j); }
+                }
+            }
+        }
+        items
+    }
+
+    /// Returns references to the `k` greatest items in the heap in descending order, without
+    /// modifying the heap.
+    ///
+    /// Returns all items if the heap holds fewer than `k` of them. This walks the heap's tree
+    /// from the root and takes `O(k log k)` time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let heap = IntervalHeap::from(vec![5, 1, 8, 3, 9, 2]);
+    /// assert_eq!(heap.largest_k(2), [&9, &8]);
+    /// assert_eq!(heap.len(), 6);
+    /// ```
+    pub fn largest_k(&self, k: usize) -> Vec<&T> {
+        debug_assert!(self.is_valid());
+        let v = self.data.as_slice();
+        let mut items = Vec::with_capacity(cmp::min(k, v.len()));
+        if k == 0 || v.is_empty() { return items; }
+        // Every item is less than or equal to the item through which it is
+        // reached: a node's left item is reached through its right item, which
+        // in turn is reached through the right item of the node's parent. The
+        // last node's only item doubles as its max.
+        let mut frontier = IntervalHeap::with_comparator(ByIndex { items: v, cmp: &self.cmp });
+        frontier.push(cmp::min(1, v.len() - 1));
+        while items.len() < k {
+            let i = match frontier.pop_max() { Some(i) => i, None => break };
+            items.push(&v[i]);
+            if i != left(i) {
+                frontier.push(i - 1);
+                for &c in &[i * 2, i * 2 + 2] {
+                    if c < v.len() { frontier.push(cmp::min(c + 1, v.len() - 1)); }
+                }
+            }
+        }
+        items
+    }
+
+    /// Returns an iterator visiting all items within the given range in arbitrary order.
+    ///
+    /// Since each node of the heap represents an interval containing all items of its
+    /// subtree, subtrees whose interval lies outside of the range are skipped entirely.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let heap = IntervalHeap::from(vec![5, 1, 8, 3, 9, 2, 7]);
+    /// let mut items: Vec<_> = heap.range(3..=7).cloned().collect();
+    /// items.sort();
+    /// assert_eq!(items, [3, 5, 7]);
+    /// ```
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> Range<'_, T, C, R, S> {
+        debug_assert!(self.is_valid());
+        Range {
+            heap: self,
+            range,
+            nodes: if self.data.is_empty() { vec![] } else { vec![0] },
+            next: 0,
+            end: 0,
+        }
+    }
+
+    /// Returns the number of items within the given range.
+    ///
+    /// This visits the same items as [`range`](#method.range).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let heap = IntervalHeap::from(vec![5, 1, 8, 3, 9, 2, 7]);
+    /// assert_eq!(heap.count_in_range(..4), 3);
+    /// assert_eq!(heap.count_in_range(6..), 3);
+    /// ```
+    pub fn count_in_range<R: RangeBounds<T>>(&self, range: R) -> usize {
+        self.range(range).count()
+    }
+
+    /// Returns the number of items the heap can hold without reallocation.
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    /// Removes the smallest item from the heap and returns it.
+    ///
+    /// Returns `None` if the heap was empty.
+    pub fn pop_min(&mut self) -> Option<T> {
+        debug_assert!(self.is_valid());
+        let min = self.take_min();
+        debug_assert!(self.is_valid());
+        min
+    }
+
+    /// Removes the greatest item from the heap and returns it.
+    ///
+    /// Returns `None` if the heap was empty.
+    pub fn pop_max(&mut self) -> Option<T> {
+        debug_assert!(self.is_valid());
+        let max = self.take_max();
+        debug_assert!(self.is_valid());
+        max
+    }
+
+    /// Removes the item in the root's min slot, restoring the heap's invariants for the
+    /// remaining items. The removed item itself is never compared.
+    fn take_min(&mut self) -> Option<T> {
+        match self.data.len() {
+            0 => None,
+            1..=2 => Some(self.data.swap_remove(0)),
+            _ => {
+                let res = self.data.swap_remove(0);
+                let (data, cmp) = (self.data.as_mut_slice(), &self.cmp);
+                match self.pop_strategy {
+                    PopStrategy::TopDown => update_min(data, cmp, &mut ()),
+                    PopStrategy::BottomUp => update_min_bottom_up(data, cmp, &mut ()),
+                }
+                Some(res)
+            }
+        }
+    }
+
+    /// Removes the item in the root's max slot, restoring the heap's invariants for the
+    /// remaining items. The removed item itself is never compared.
+    fn take_max(&mut self) -> Option<T> {
+        match self.data.len() {
+            0..=2 => self.data.pop(),
+            _ => {
+                let res = self.data.swap_remove(1);
+                let (data, cmp) = (self.data.as_mut_slice(), &self.cmp);
+                match self.pop_strategy {
+                    PopStrategy::TopDown => update_max(data, cmp, &mut ()),
+                    PopStrategy::BottomUp => update_max_bottom_up(data, cmp, &mut ()),
+                }
+                Some(res)
+            }
+        }
+    }
+
+    /// Pushes an item onto the heap.
+    pub fn push(&mut self, item: T) {
+        debug_assert!(self.is_valid());
+        self.data.push(item);
+        interval_heap_push(self.data.as_mut_slice(), &self.cmp, &mut ());
+        debug_assert!(self.is_valid());
+    }
+
+    /// Pushes an item onto the heap, then removes the smallest item and returns it.
+    ///
+    /// This is more efficient than calling `push` and `pop_min` separately: if `item` would be
+    /// the smallest item, it is returned right away; otherwise it takes the smallest item's
+    /// place in a single pass through the heap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut heap = IntervalHeap::from(vec![3, 5]);
+    /// assert_eq!(heap.push_pop_min(1), 1);
+    /// assert_eq!(heap.push_pop_min(4), 3);
+    /// assert_eq!(heap.min_max(), Some((&4, &5)));
+    /// ```
+    pub fn push_pop_min(&mut self, item: T) -> T {
+        match self.min() {
+            Some(min) if self.cmp.compares_gt(&item, min) => self.replace_min_item(item),
+            _ => item,
+        }
+    }
+
+    /// Pushes an item onto the heap, then removes the greatest item and returns it.
+    ///
+    /// This is more efficient than calling `push` and `pop_max` separately: if `item` would be
+    /// the greatest item, it is returned right away; otherwise it takes the greatest item's
+    /// place in a single pass through the heap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut heap = IntervalHeap::from(vec![3, 5]);
+    /// assert_eq!(heap.push_pop_max(6), 6);
+    /// assert_eq!(heap.push_pop_max(4), 5);
+    /// assert_eq!(heap.min_max(), Some((&3, &4)));
+    /// ```
+    pub fn push_pop_max(&mut self, item: T) -> T {
+        match self.max() {
+            Some(max) if self.cmp.compares_lt(&item, max) => self.replace_max_item(item),
+            _ => item,
+        }
+    }
+
+    /// Removes the smallest item from the heap, then pushes `item` onto the heap.
+    ///
+    /// Returns the removed item, or `None` if the heap was empty. This is more efficient than
+    /// calling `pop_min` and `push` separately, as `item` takes the smallest item's place in a
+    /// single pass through the heap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut heap = IntervalHeap::new();
+    /// assert_eq!(heap.replace_min(3), None);
+    /// assert_eq!(heap.replace_min(1), Some(3));
+    /// assert_eq!(heap.min_max(), Some((&1, &1)));
+    /// ```
+    pub fn replace_min(&mut self, item: T) -> Option<T> {
+        if self.is_empty() {
+            self.push(item);
+            None
+        } else {
+            Some(self.replace_min_item(item))
+        }
+    }
+
+    /// Removes the greatest item from the heap, then pushes `item` onto the heap.
+    ///
+    /// Returns the removed item, or `None` if the heap was empty. This is more efficient than
+    /// calling `pop_max` and `push` separately, as `item` takes the greatest item's place in a
+    /// single pass through the heap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut heap = IntervalHeap::new();
+    /// assert_eq!(heap.replace_max(3), None);
+    /// assert_eq!(heap.replace_max(5), Some(3));
+    /// assert_eq!(heap.min_max(), Some((&5, &5)));
+    /// ```
+    pub fn replace_max(&mut self, item: T) -> Option<T> {
+        if self.is_empty() {
+            self.push(item);
+            None
+        } else {
+            Some(self.replace_max_item(item))
+        }
+    }
+
+    /// Replaces the smallest item of the non-empty heap with `item` and returns it.
+    fn replace_min_item(&mut self, item: T) -> T {
+        debug_assert!(self.is_valid());
+        let v = self.data.as_mut_slice();
+        let min = mem::replace(&mut v[0], item);
+        replaced_min(v, &self.cmp);
+        debug_assert!(self.is_valid());
+        min
+    }
+
+    /// Replaces the greatest item of the non-empty heap with `item` and returns it.
+    fn replace_max_item(&mut self, item: T) -> T {
+        debug_assert!(self.is_valid());
+        let v = self.data.as_mut_slice();
+        let i = cmp::min(1, v.len() - 1);
+        let max = mem::replace(&mut v[i], item);
+        replaced_max(v, &self.cmp);
+        debug_assert!(self.is_valid());
+        max
+    }
+
+    /// Removes the first item matching the given predicate from the heap and returns it.
+    ///
+    /// Items are tested in arbitrary order. Returns `None` if no item matches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut heap = IntervalHeap::from(vec![1, 4, 9, 16]);
+    /// assert_eq!(heap.remove_where(|&x| x % 2 == 1 && x > 1), Some(9));
+    /// assert_eq!(heap.remove_where(|&x| x > 20), None);
+    /// assert_eq!(heap.into_sorted_vec(), [1, 4, 16]);
+    /// ```
+    pub fn remove_where<F: FnMut(&T) -> bool>(&mut self, pred: F) -> Option<T> {
+        debug_assert!(self.is_valid());
+        let item = self.data.as_slice().iter().position(pred)
+            .map(|i| interval_heap_remove(&mut self.data, &self.cmp, i, &mut ()));
+        debug_assert!(self.is_valid());
+        item
+    }
+
+    /// Consumes the heap and returns an iterator yielding its items in sorted (ascending)
+    /// order.
+    ///
+    /// Unlike [`into_sorted_vec`](#method.into_sorted_vec), items are removed from the heap
+    /// lazily, one `O(log n)` pop at a time. The iterator is double-ended: `next_back` yields
+    /// the items in descending order, so both ends can be consumed from the same iterator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let heap = IntervalHeap::from(vec![3, 1, 4, 1, 5]);
+    /// let mut iter = heap.into_iter_sorted();
+    /// assert_eq!(iter.next(), Some(1));
+    /// assert_eq!(iter.next_back(), Some(5));
+    /// assert_eq!(iter.len(), 3);
+    /// assert_eq!(iter.collect::<Vec<_>>(), [1, 3, 4]);
+    /// ```
+    pub fn into_iter_sorted(self) -> IntoIterSorted<T, C, S> {
+        IntoIterSorted { heap: self }
+    }
+
+    /// Consumes the heap and returns an iterator yielding its items in reverse sorted
+    /// (descending) order.
+    ///
+    /// This is the reverse of [`into_iter_sorted`](#method.into_iter_sorted).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let heap = IntervalHeap::from(vec![3, 1, 4, 1, 5]);
+    /// assert_eq!(heap.into_iter_sorted_desc().take(2).collect::<Vec<_>>(), [5, 4]);
+    /// ```
+    pub fn into_iter_sorted_desc(self) -> iter::Rev<IntoIterSorted<T, C, S>> {
+        self.into_iter_sorted().rev()
+    }
+
     /// Returns the number of items in the heap.
     pub fn len(&self) -> usize {
         self.data.len()
     }
 
-    /// Returns `true` if the heap contains no items.
-    pub fn is_empty(&self) -> bool {
-        self.data.is_empty()
+    /// Returns `true` if the heap contains no items.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Removes all items from the heap.
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    /// Returns an iterator that removes the heap's items in sorted (ascending) order.
+    ///
+    /// Items are removed lazily, one `O(log n)` pop at a time, and the iterator is
+    /// double-ended: `next_back` removes the greatest remaining item. If the iterator is
+    /// dropped before it is exhausted, the items it did not yield remain in the heap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut heap = IntervalHeap::from(vec![7, 2, 9, 4, 1]);
+    /// let below_five: Vec<_> = heap.drain_sorted().take_while(|&x| x < 5).collect();
+    /// assert_eq!(below_five, [1, 2, 4]);
+    /// // `take_while` consumed the 7 to find the end of the range.
+    /// assert_eq!(heap.into_sorted_vec(), [9]);
+    /// ```
+    pub fn drain_sorted(&mut self) -> DrainSorted<'_, T, C, S> {
+        DrainSorted { heap: self }
+    }
+
+    /// Returns an iterator that removes the heap's items in reverse sorted (descending) order.
+    ///
+    /// This is the reverse of [`drain_sorted`](#method.drain_sorted).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut heap = IntervalHeap::from(vec![7, 2, 9, 4, 1]);
+    /// assert_eq!(heap.drain_sorted_rev().take(2).collect::<Vec<_>>(), [9, 7]);
+    /// assert_eq!(heap.into_sorted_vec(), [1, 2, 4]);
+    /// ```
+    pub fn drain_sorted_rev(&mut self) -> iter::Rev<DrainSorted<'_, T, C, S>> {
+        self.drain_sorted().rev()
+    }
+
+    /// Returns an iterator that removes the heap's smallest items, in ascending order, for as
+    /// long as they satisfy the given predicate.
+    ///
+    /// Items are removed lazily; the first item failing the predicate, and all items after
+    /// it, remain in the heap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut deadlines = IntervalHeap::from(vec![30, 10, 50, 20, 40]);
+    /// let now = 25;
+    /// let due: Vec<_> = deadlines.pop_min_while(|&d| d <= now).collect();
+    /// assert_eq!(due, [10, 20]);
+    /// assert_eq!(deadlines.min(), Some(&30));
+    /// ```
+    pub fn pop_min_while<F: FnMut(&T) -> bool>(&mut self, pred: F) -> PopMinWhile<'_, T, C, F, S> {
+        PopMinWhile { heap: self, pred }
+    }
+
+    /// Returns an iterator that removes the heap's greatest items, in descending order, for as
+    /// long as they satisfy the given predicate.
+    ///
+    /// Items are removed lazily; the first item failing the predicate, and all items after
+    /// it, remain in the heap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut heap = IntervalHeap::from(vec![30, 10, 50, 20, 40]);
+    /// assert_eq!(heap.pop_max_while(|&x| x > 35).collect::<Vec<_>>(), [50, 40]);
+    /// assert_eq!(heap.max(), Some(&30));
+    /// ```
+    pub fn pop_max_while<F: FnMut(&T) -> bool>(&mut self, pred: F) -> PopMaxWhile<'_, T, C, F, S> {
+        PopMaxWhile { heap: self, pred }
     }
 
-    /// Removes all items from the heap.
-    pub fn clear(&mut self) {
-        self.data.clear();
+    /// Removes all items less than `bound` from the heap and returns them as a new heap with
+    /// the same comparator and pop strategy.
+    ///
+    /// This takes `O(k log n)` time, where `k` is the number of removed items.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut heap = IntervalHeap::from(vec![5, 1, 8, 3, 9]);
+    /// let below = heap.split_off_below(&5);
+    /// assert_eq!(below.into_sorted_vec(), [1, 3]);
+    /// assert_eq!(heap.into_sorted_vec(), [5, 8, 9]);
+    /// ```
+    pub fn split_off_below(&mut self, bound: &T) -> IntervalHeap<T, C> where C: Clone {
+        let cmp = self.cmp.clone();
+        let items = self.pop_min_while(|item| cmp.compares_lt(item, bound)).collect();
+        let mut below = IntervalHeap::from_vec_and_comparator(items, cmp);
+        below.pop_strategy = self.pop_strategy;
+        below
     }
 
-    /// Clears the heap, returning an iterator over the removed items in arbitrary order.
-    pub fn drain(&mut self) -> Drain<T> {
-        Drain(self.data.drain(..))
+    /// Removes all items greater than `bound` from the heap and returns them as a new heap
+    /// with the same comparator and pop strategy.
+    ///
+    /// This takes `O(k log n)` time, where `k` is the number of removed items.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let mut heap = IntervalHeap::from(vec![5, 1, 8, 3, 9]);
+    /// let above = heap.split_off_above(&5);
+    /// assert_eq!(above.into_sorted_vec(), [8, 9]);
+    /// assert_eq!(heap.into_sorted_vec(), [1, 3, 5]);
+    /// ```
+    pub fn split_off_above(&mut self, bound: &T) -> IntervalHeap<T, C> where C: Clone {
+        let cmp = self.cmp.clone();
+        let items = self.pop_max_while(|item| cmp.compares_gt(item, bound)).collect();
+        let mut above = IntervalHeap::from_vec_and_comparator(items, cmp);
+        above.pop_strategy = self.pop_strategy;
+        above
     }
 
     /// Checks if the heap is valid.
     ///
     /// The heap is valid if:
     ///
-    /// 1. It has fewer than two items, OR
-    /// 2a. Each node's left item is less than or equal to its right item, AND
-    /// 2b. Each node's left item is greater than or equal to the left item of the
-    ///     node's parent, AND
-    /// 2c. Each node's right item is less than or equal to the right item of the
-    ///     node's parent
+    /// - (1) It has fewer than two items, OR
+    /// - (2a) Each node's left item is less than or equal to its right item, AND
+    /// - (2b) Each node's left item is greater than or equal to the left item of the
+    ///   node's parent, AND
+    /// - (2c) Each node's right item is less than or equal to the right item of the
+    ///   node's parent
     fn is_valid(&self) -> bool {
-        let mut nodes = self.data.chunks(2);
+        let v = self.data.as_slice();
+        let mut nodes = v.chunks(2);
 
         match nodes.next() {
             Some(chunk) if chunk.len() == 2 => {
@@ -410,8 +1563,8 @@ impl<T, C: Compare<T>> IntervalHeap<T, C> {
                     let r = node.last().unwrap();
 
                     self.cmp.compares_le(l, r) &&              // 2a
-                    self.cmp.compares_ge(l, &self.data[p]) &&  // 2b
-                    self.cmp.compares_le(r, &self.data[p + 1]) // 2c
+                    self.cmp.compares_ge(l, &v[p]) &&  // 2b
+                    self.cmp.compares_le(r, &v[p + 1]) // 2c
                 })
             }
             _ => true, // 1
@@ -419,7 +1572,7 @@ impl<T, C: Compare<T>> IntervalHeap<T, C> {
     }
 }
 
-impl<T: Debug, C: Compare<T>> Debug for IntervalHeap<T, C> {
+impl<T: Debug, C: Compare<T>, S: HeapStorage<T>> Debug for IntervalHeap<T, C, S> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_list().entries(self).finish()
     }
@@ -431,20 +1584,108 @@ impl<T, C: Compare<T> + Default> iter::FromIterator<T> for IntervalHeap<T, C> {
     }
 }
 
-impl<T, C: Compare<T>> Extend<T> for IntervalHeap<T, C> {
+impl<T, C: Compare<T>, S: HeapStorage<T>> Extend<T> for IntervalHeap<T, C, S> {
     fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
         let iter = iter.into_iter();
         let (lower, _) = iter.size_hint();
-        self.reserve(lower);
+        self.data.reserve(lower);
         for elem in iter {
             self.push(elem);
         }
     }
 }
 
-impl<'a, T: 'a + Copy, C: Compare<T>> Extend<&'a T> for IntervalHeap<T, C> {
+impl<'a, T: 'a + Copy, C: Compare<T>, S: HeapStorage<T>> Extend<&'a T>
+        for IntervalHeap<T, C, S> {
     fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
-        self.extend(iter.into_iter().map(|&item| item));
+        self.extend(iter.into_iter().cloned());
+    }
+}
+
+/// A mutable reference to the smallest item of an `IntervalHeap`.
+///
+/// Acquire through [`IntervalHeap::peek_min_mut`](struct.IntervalHeap.html#method.peek_min_mut).
+pub struct PeekMin<'a, T: 'a, C: 'a + Compare<T>, S: 'a + HeapStorage<T> = Vec<T>> {
+    heap: &'a mut IntervalHeap<T, C, S>,
+    sift: bool,
+}
+
+impl<'a, T, C: Compare<T>, S: HeapStorage<T>> PeekMin<'a, T, C, S> {
+    /// Removes the peeked item from the heap and returns it.
+    pub fn pop(mut this: PeekMin<'a, T, C, S>) -> T {
+        this.sift = false;
+        this.heap.take_min().unwrap()
+    }
+}
+
+impl<'a, T, C: Compare<T>, S: HeapStorage<T>> Deref for PeekMin<'a, T, C, S> {
+    type Target = T;
+    fn deref(&self) -> &T { &self.heap.data.as_slice()[0] }
+}
+
+impl<'a, T, C: Compare<T>, S: HeapStorage<T>> DerefMut for PeekMin<'a, T, C, S> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.sift = true;
+        &mut self.heap.data.as_mut_slice()[0]
+    }
+}
+
+impl<'a, T, C: Compare<T>, S: HeapStorage<T>> Drop for PeekMin<'a, T, C, S> {
+    fn drop(&mut self) {
+        if !self.sift { return; }
+        replaced_min(self.heap.data.as_mut_slice(), &self.heap.cmp);
+        debug_assert!(self.heap.is_valid());
+    }
+}
+
+impl<'a, T: Debug, C: Compare<T>, S: HeapStorage<T>> Debug for PeekMin<'a, T, C, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("PeekMin").field(&**self).finish()
+    }
+}
+
+/// A mutable reference to the greatest item of an `IntervalHeap`.
+///
+/// Acquire through [`IntervalHeap::peek_max_mut`](struct.IntervalHeap.html#method.peek_max_mut).
+pub struct PeekMax<'a, T: 'a, C: 'a + Compare<T>, S: 'a + HeapStorage<T> = Vec<T>> {
+    heap: &'a mut IntervalHeap<T, C, S>,
+    sift: bool,
+}
+
+impl<'a, T, C: Compare<T>, S: HeapStorage<T>> PeekMax<'a, T, C, S> {
+    /// Removes the peeked item from the heap and returns it.
+    pub fn pop(mut this: PeekMax<'a, T, C, S>) -> T {
+        this.sift = false;
+        this.heap.take_max().unwrap()
+    }
+
+    fn index(&self) -> usize { cmp::min(1, self.heap.data.len() - 1) }
+}
+
+impl<'a, T, C: Compare<T>, S: HeapStorage<T>> Deref for PeekMax<'a, T, C, S> {
+    type Target = T;
+    fn deref(&self) -> &T { &self.heap.data.as_slice()[self.index()] }
+}
+
+impl<'a, T, C: Compare<T>, S: HeapStorage<T>> DerefMut for PeekMax<'a, T, C, S> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.sift = true;
+        let i = self.index();
+        &mut self.heap.data.as_mut_slice()[i]
+    }
+}
+
+impl<'a, T, C: Compare<T>, S: HeapStorage<T>> Drop for PeekMax<'a, T, C, S> {
+    fn drop(&mut self) {
+        if !self.sift { return; }
+        replaced_max(self.heap.data.as_mut_slice(), &self.heap.cmp);
+        debug_assert!(self.heap.is_valid());
+    }
+}
+
+impl<'a, T: Debug, C: Compare<T>, S: HeapStorage<T>> Debug for PeekMax<'a, T, C, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("PeekMax").field(&**self).finish()
     }
 }
 
@@ -469,6 +1710,64 @@ impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
 
 impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
 
+/// An iterator over the items of an `IntervalHeap` within a range, in arbitrary order.
+///
+/// Acquire through [`IntervalHeap::range`](struct.IntervalHeap.html#method.range).
+pub struct Range<'a, T: 'a, C: 'a + Compare<T>, R, S: 'a + HeapStorage<T> = Vec<T>> {
+    heap: &'a IntervalHeap<T, C, S>,
+    range: R,
+    /// The left item indices of the nodes still to be visited.
+    nodes: Vec<usize>,
+    /// The remaining items of the node being visited.
+    next: usize,
+    end: usize,
+}
+
+impl<'a, T, C: Compare<T>, R: RangeBounds<T>, S: HeapStorage<T>> Range<'a, T, C, R, S> {
+    fn above_start(&self, item: &T) -> bool {
+        match self.range.start_bound() {
+            Bound::Included(start) => self.heap.cmp.compares_ge(item, start),
+            Bound::Excluded(start) => self.heap.cmp.compares_gt(item, start),
+            Bound::Unbounded => true,
+        }
+    }
+
+    fn below_end(&self, item: &T) -> bool {
+        match self.range.end_bound() {
+            Bound::Included(end) => self.heap.cmp.compares_le(item, end),
+            Bound::Excluded(end) => self.heap.cmp.compares_lt(item, end),
+            Bound::Unbounded => true,
+        }
+    }
+}
+
+impl<'a, T, C: Compare<T>, R: RangeBounds<T>, S: HeapStorage<T>> Iterator
+        for Range<'a, T, C, R, S> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let v = self.heap.data.as_slice();
+        loop {
+            while self.next < self.end {
+                let item = &v[self.next];
+                self.next += 1;
+                if self.above_start(item) && self.below_end(item) { return Some(item); }
+            }
+            let node = self.nodes.pop()?;
+            let max = cmp::min(node + 1, v.len() - 1);
+            // Skip the whole subtree if its interval is disjoint from the range.
+            if !self.above_start(&v[max]) || !self.below_end(&v[node]) { continue; }
+            self.next = node;
+            self.end = max + 1;
+            for &child in &[node * 2 + 2, node * 2 + 4] {
+                if child < v.len() { self.nodes.push(child); }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { (0, Some(self.heap.len())) }
+}
+
 /// A consuming iterator over an `IntervalHeap` in arbitrary order.
 ///
 /// Acquire through [`IntoIterator::into_iter`](
@@ -487,6 +1786,27 @@ impl<T> DoubleEndedIterator for IntoIter<T> {
 
 impl<T> ExactSizeIterator for IntoIter<T> {}
 
+/// A consuming iterator over an `IntervalHeap` in sorted order.
+///
+/// Acquire through [`IntervalHeap::into_iter_sorted`](
+/// struct.IntervalHeap.html#method.into_iter_sorted).
+#[derive(Clone)]
+pub struct IntoIterSorted<T, C: Compare<T> = Natural<T>, S: HeapStorage<T> = Vec<T>> {
+    heap: IntervalHeap<T, C, S>,
+}
+
+impl<T, C: Compare<T>, S: HeapStorage<T>> Iterator for IntoIterSorted<T, C, S> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> { self.heap.pop_min() }
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.heap.len(), Some(self.heap.len())) }
+}
+
+impl<T, C: Compare<T>, S: HeapStorage<T>> DoubleEndedIterator for IntoIterSorted<T, C, S> {
+    fn next_back(&mut self) -> Option<T> { self.heap.pop_max() }
+}
+
+impl<T, C: Compare<T>, S: HeapStorage<T>> ExactSizeIterator for IntoIterSorted<T, C, S> {}
+
 /// An iterator that drains an `IntervalHeap` in arbitrary oder.
 ///
 /// Acquire through [`IntervalHeap::drain`](struct.IntervalHeap.html#method.drain).
@@ -504,13 +1824,79 @@ impl<'a, T: 'a> DoubleEndedIterator for Drain<'a, T> {
 
 impl<'a, T: 'a> ExactSizeIterator for Drain<'a, T> {}
 
+/// An iterator that drains an `IntervalHeap` in sorted order.
+///
+/// Acquire through [`IntervalHeap::drain_sorted`](struct.IntervalHeap.html#method.drain_sorted).
+/// Items that have not been yielded when the iterator is dropped remain in the heap.
+pub struct DrainSorted<'a, T: 'a, C: 'a + Compare<T>, S: 'a + HeapStorage<T> = Vec<T>> {
+    heap: &'a mut IntervalHeap<T, C, S>,
+}
+
+impl<'a, T: 'a, C: Compare<T>, S: HeapStorage<T>> Iterator for DrainSorted<'a, T, C, S> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> { self.heap.pop_min() }
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.heap.len(), Some(self.heap.len())) }
+}
+
+impl<'a, T: 'a, C: Compare<T>, S: HeapStorage<T>> DoubleEndedIterator for DrainSorted<'a, T, C, S> {
+    fn next_back(&mut self) -> Option<T> { self.heap.pop_max() }
+}
+
+impl<'a, T: 'a, C: Compare<T>, S: HeapStorage<T>> ExactSizeIterator for DrainSorted<'a, T, C, S> {}
+
+/// An iterator that removes the smallest items of an `IntervalHeap` while they satisfy a
+/// predicate.
+///
+/// Acquire through [`IntervalHeap::pop_min_while`](struct.IntervalHeap.html#method.pop_min_while).
+pub struct PopMinWhile<'a, T: 'a, C: 'a + Compare<T>, F, S: 'a + HeapStorage<T> = Vec<T>> {
+    heap: &'a mut IntervalHeap<T, C, S>,
+    pred: F,
+}
+
+impl<'a, T: 'a, C: Compare<T>, F: FnMut(&T) -> bool, S: HeapStorage<T>> Iterator
+        for PopMinWhile<'a, T, C, F, S> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match self.heap.min() {
+            Some(min) if (self.pred)(min) => self.heap.pop_min(),
+            _ => None,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { (0, Some(self.heap.len())) }
+}
+
+/// An iterator that removes the greatest items of an `IntervalHeap` while they satisfy a
+/// predicate.
+///
+/// Acquire through [`IntervalHeap::pop_max_while`](struct.IntervalHeap.html#method.pop_max_while).
+pub struct PopMaxWhile<'a, T: 'a, C: 'a + Compare<T>, F, S: 'a + HeapStorage<T> = Vec<T>> {
+    heap: &'a mut IntervalHeap<T, C, S>,
+    pred: F,
+}
+
+impl<'a, T: 'a, C: Compare<T>, F: FnMut(&T) -> bool, S: HeapStorage<T>> Iterator
+        for PopMaxWhile<'a, T, C, F, S> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match self.heap.max() {
+            Some(max) if (self.pred)(max) => self.heap.pop_max(),
+            _ => None,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { (0, Some(self.heap.len())) }
+}
+
 impl<T, C: Compare<T>> IntoIterator for IntervalHeap<T, C> {
     type Item = T;
     type IntoIter = IntoIter<T>;
     fn into_iter(self) -> IntoIter<T> { IntoIter(self.data.into_iter()) }
 }
 
-impl<'a, T, C: Compare<T>> IntoIterator for &'a IntervalHeap<T, C> {
+impl<'a, T, C: Compare<T>, S: HeapStorage<T>> IntoIterator for &'a IntervalHeap<T, C, S> {
     type Item = &'a T;
     type IntoIter = Iter<'a, T>;
     fn into_iter(self) -> Iter<'a, T> { self.iter() }
@@ -518,8 +1904,13 @@ impl<'a, T, C: Compare<T>> IntoIterator for &'a IntervalHeap<T, C> {
 
 #[cfg(test)]
 mod test {
+    use std::cell::Cell;
+    use std::cmp::Ordering;
+    use std::marker::PhantomData;
+    use std::panic::{self, AssertUnwindSafe};
+    use compare::Compare;
     use rand::{thread_rng, Rng};
-    use super::IntervalHeap;
+    use super::{IntervalHeap, PeekMax, PeekMin, PopStrategy};
 
     #[test]
     fn fuzz_push_into_sorted_vec() {
@@ -586,6 +1977,410 @@ mod test {
         }
     }
 
+    #[test]
+    fn fuzz_pop_bottom_up() {
+        let mut rng = thread_rng();
+        for len in 0..50 {
+            let vec: Vec<u32> = (0..len).map(|_| rng.gen_range(0, 20)).collect();
+            let mut sorted = vec.clone();
+            sorted.sort();
+            let mut ih = IntervalHeap::from_vec_with_pop_strategy(vec, PopStrategy::BottomUp);
+            while !sorted.is_empty() {
+                if rng.gen() {
+                    assert_eq!(ih.pop_min(), Some(sorted.remove(0)));
+                } else {
+                    assert_eq!(ih.pop_max(), sorted.pop());
+                }
+                assert!(ih.is_valid());
+                if rng.gen_weighted_bool(3) {
+                    let x = rng.gen_range(0, 20);
+                    ih.push(x);
+                    let i = sorted.binary_search(&x).unwrap_or_else(|i| i);
+                    sorted.insert(i, x);
+                }
+            }
+            assert_eq!(ih.pop_min(), None);
+            assert_eq!(ih.pop_max(), None);
+        }
+    }
+
+    /// Compares numbers, counting the comparisons.
+    struct CountingCmp<'a> {
+        count: &'a Cell<usize>,
+    }
+
+    impl<'a> Compare<u32> for CountingCmp<'a> {
+        fn compare(&self, a: &u32, b: &u32) -> Ordering {
+            self.count.set(self.count.get() + 1);
+            a.cmp(b)
+        }
+    }
+
+    #[test]
+    fn test_bottom_up_comparisons() {
+        let mut rng = thread_rng();
+        let vec: Vec<u32> = (0..1000).map(|_| rng.next_u32()).collect();
+        let mut counts = vec![];
+        for &bottom_up in &[false, true] {
+            // Work on the raw items, as the heap checks itself with the comparator in debug
+            // builds.
+            let count = Cell::new(0);
+            let cmp = CountingCmp { count: &count };
+            let mut v = vec.clone();
+            super::heapify(&mut v, &cmp);
+            count.set(0);
+            for pop_min in [true, false].iter().cycle() {
+                if v.len() <= 2 { break; }
+                if *pop_min {
+                    v.swap_remove(0);
+                    if bottom_up { super::update_min_bottom_up(&mut v, &cmp, &mut ()); }
+                    else { super::update_min(&mut v, &cmp, &mut ()); }
+                } else {
+                    v.swap_remove(1);
+                    if bottom_up { super::update_max_bottom_up(&mut v, &cmp, &mut ()); }
+                    else { super::update_max(&mut v, &cmp, &mut ()); }
+                }
+            }
+            counts.push(count.get());
+        }
+        assert!(counts[1] < counts[0] * 3 / 4, "{:?}", counts);
+    }
+
+    #[test]
+    fn fuzz_from_vec() {
+        let mut rng = thread_rng();
+        for len in 0..100 {
+            let vec: Vec<u32> = (0..len).map(|_| rng.gen_range(0, 50)).collect();
+            let ih = IntervalHeap::from(vec);
+            assert!(ih.is_valid());
+            assert_eq!(ih.len(), len);
+            let sorted = ih.into_sorted_vec();
+            for pair in sorted.windows(2) {
+                assert!(pair[0] <= pair[1]);
+            }
+        }
+    }
+
+    #[test]
+    fn fuzz_from_iter_pop() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let mut ih: IntervalHeap<u32> = (0..100).map(|_| rng.next_u32()).collect();
+            let mut lo = 0;
+            let mut hi = u32::MAX;
+            while !ih.is_empty() {
+                if rng.gen() {
+                    let x = ih.pop_min().unwrap();
+                    assert!(lo <= x);
+                    lo = x;
+                } else {
+                    let x = ih.pop_max().unwrap();
+                    assert!(x <= hi);
+                    hi = x;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fuzz_peek_mut() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let mut ih: IntervalHeap<u32> = (0..50).map(|_| rng.gen_range(0, 100)).collect();
+            for _ in 0..50 {
+                let x = rng.gen_range(0, 100);
+                if rng.gen() {
+                    *ih.peek_min_mut().unwrap() = x;
+                } else {
+                    *ih.peek_max_mut().unwrap() = x;
+                }
+                assert!(ih.is_valid());
+            }
+        }
+    }
+
+    #[test]
+    fn test_peek_mut_pop() {
+        let mut ih = IntervalHeap::from(vec![4, 1, 3, 2]);
+        {
+            let mut min = ih.peek_min_mut().unwrap();
+            *min = 9;
+            assert_eq!(PeekMin::pop(min), 9);
+        }
+        assert_eq!(ih.min_max(), Some((&2, &4)));
+        assert_eq!(PeekMax::pop(ih.peek_max_mut().unwrap()), 4);
+        assert_eq!(PeekMin::pop(ih.peek_min_mut().unwrap()), 2);
+        assert_eq!(*ih.peek_max_mut().unwrap(), 3);
+        assert_eq!(PeekMax::pop(ih.peek_max_mut().unwrap()), 3);
+        assert!(ih.peek_min_mut().is_none());
+    }
+
+    #[test]
+    fn fuzz_retain() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let len = rng.gen_range(0, 100);
+            let mut ih: IntervalHeap<u32> = (0..len).map(|_| rng.gen_range(0, 100)).collect();
+            let mut expected: Vec<u32> = ih.iter().cloned().filter(|x| x % 3 != 0).collect();
+            expected.sort();
+            let mut calls = 0;
+            ih.retain(|x| { calls += 1; x % 3 != 0 });
+            assert_eq!(calls, len);
+            assert!(ih.is_valid());
+            assert_eq!(ih.into_sorted_vec(), expected);
+        }
+    }
+
+    #[test]
+    fn fuzz_retain_mut() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let mut ih: IntervalHeap<u32> = (0..100).map(|_| rng.gen_range(0, 100)).collect();
+            let mut expected: Vec<u32> = ih.iter().filter(|&&x| x % 2 == 0)
+                                           .map(|&x| 100 - x).collect();
+            expected.sort();
+            ih.retain_mut(|x| { *x = 100 - *x; *x % 2 == 0 });
+            assert!(ih.is_valid());
+            assert_eq!(ih.into_sorted_vec(), expected);
+        }
+    }
+
+    #[test]
+    fn fuzz_remove_where() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let mut ih: IntervalHeap<u32> = (0..50).map(|_| rng.gen_range(0, 100)).collect();
+            for _ in 0..25 {
+                let x = rng.gen_range(0, 100);
+                let found = ih.iter().any(|&y| y == x);
+                assert_eq!(ih.remove_where(|&y| y == x), if found { Some(x) } else { None });
+                assert!(ih.is_valid());
+            }
+        }
+    }
+
+    #[test]
+    fn fuzz_append() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let len1 = rng.gen_range(0, 200);
+            let len2 = rng.gen_range(0, 200);
+            let mut a: IntervalHeap<u32> = (0..len1).map(|_| rng.gen_range(0, 100)).collect();
+            let mut b: IntervalHeap<u32> = (0..len2).map(|_| rng.gen_range(0, 100)).collect();
+            let mut expected: Vec<u32> = a.iter().chain(b.iter()).cloned().collect();
+            expected.sort();
+            a.append(&mut b);
+            assert!(a.is_valid());
+            assert!(b.is_empty());
+            assert_eq!(a.into_sorted_vec(), expected);
+        }
+    }
+
+    #[test]
+    fn fuzz_push_pop_replace() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let mut ih = IntervalHeap::new();
+            let mut sorted: Vec<u32> = vec![];
+            for _ in 0..100 {
+                let x = rng.gen_range(0, 50);
+                match rng.gen_range(0, 4) {
+                    0 => {
+                        sorted.push(x);
+                        sorted.sort();
+                        assert_eq!(ih.push_pop_min(x), sorted.remove(0));
+                    }
+                    1 => {
+                        sorted.push(x);
+                        sorted.sort();
+                        assert_eq!(ih.push_pop_max(x), sorted.pop().unwrap());
+                    }
+                    2 => {
+                        let min = if sorted.is_empty() { None } else { Some(sorted.remove(0)) };
+                        assert_eq!(ih.replace_min(x), min);
+                        sorted.push(x);
+                        sorted.sort();
+                    }
+                    _ => {
+                        let max = sorted.pop();
+                        assert_eq!(ih.replace_max(x), max);
+                        sorted.push(x);
+                        sorted.sort();
+                    }
+                }
+                assert!(ih.is_valid());
+                assert_eq!(ih.iter().count(), sorted.len());
+            }
+            assert_eq!(ih.into_sorted_vec(), sorted);
+        }
+    }
+
+    #[test]
+    fn fuzz_into_iter_sorted() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let ih: IntervalHeap<u32> = (0..100).map(|_| rng.gen_range(0, 50)).collect();
+            let mut sorted = ih.clone().into_sorted_vec();
+            let mut iter = ih.into_iter_sorted();
+            while !sorted.is_empty() {
+                assert_eq!(iter.size_hint(), (sorted.len(), Some(sorted.len())));
+                if rng.gen() {
+                    assert_eq!(iter.next(), Some(sorted.remove(0)));
+                } else {
+                    assert_eq!(iter.next_back(), sorted.pop());
+                }
+            }
+            assert_eq!(iter.next(), None);
+            assert_eq!(iter.next_back(), None);
+        }
+    }
+
+    #[test]
+    fn fuzz_drain_sorted() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let mut ih: IntervalHeap<u32> = (0..100).map(|_| rng.gen_range(0, 50)).collect();
+            let mut sorted = ih.clone().into_sorted_vec();
+            {
+                let mut drain = ih.drain_sorted();
+                for _ in 0..rng.gen_range(0, 100) {
+                    assert_eq!(drain.len(), sorted.len());
+                    if rng.gen() {
+                        assert_eq!(drain.next(), Some(sorted.remove(0)));
+                    } else {
+                        assert_eq!(drain.next_back(), sorted.pop());
+                    }
+                }
+            }
+            assert!(ih.is_valid());
+            assert_eq!(ih.into_sorted_vec(), sorted);
+        }
+    }
+
+    #[test]
+    fn fuzz_split_off() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let mut ih: IntervalHeap<u32> = (0..100).map(|_| rng.gen_range(0, 50)).collect();
+            let sorted = ih.clone().into_sorted_vec();
+            let lo = rng.gen_range(0, 50);
+            let hi = rng.gen_range(lo, 50);
+            let below = ih.split_off_below(&lo);
+            let above = ih.split_off_above(&hi);
+            assert!(below.is_valid() && above.is_valid() && ih.is_valid());
+            let mut all = below.into_sorted_vec();
+            assert!(all.iter().all(|&x| x < lo));
+            let rest = ih.into_sorted_vec();
+            assert!(rest.iter().all(|&x| lo <= x && x <= hi));
+            all.extend(rest);
+            let above = above.into_sorted_vec();
+            assert!(above.iter().all(|&x| x > hi));
+            all.extend(above);
+            assert_eq!(all, sorted);
+        }
+    }
+
+    #[test]
+    fn test_pop_while() {
+        let mut ih = IntervalHeap::from(vec![4, 8, 1, 6, 3, 9]);
+        assert_eq!(ih.pop_min_while(|&x| x < 5).collect::<Vec<_>>(), [1, 3, 4]);
+        assert_eq!(ih.pop_max_while(|&x| x > 7).collect::<Vec<_>>(), [9, 8]);
+        assert_eq!(ih.pop_min_while(|_| false).next(), None);
+        assert_eq!(ih.pop_max_while(|_| true).collect::<Vec<_>>(), [6]);
+        assert!(ih.is_empty());
+    }
+
+    #[test]
+    fn fuzz_smallest_largest_k() {
+        let mut rng = thread_rng();
+        for len in 0..100 {
+            let ih: IntervalHeap<u32> = (0..len).map(|_| rng.gen_range(0, 50)).collect();
+            let sorted = ih.clone().into_sorted_vec();
+            let k = rng.gen_range(0, len + 5);
+            let smallest: Vec<u32> = ih.smallest_k(k).into_iter().cloned().collect();
+            assert_eq!(smallest, &sorted[..k.min(len)]);
+            let mut largest: Vec<u32> = ih.largest_k(k).into_iter().cloned().collect();
+            largest.reverse();
+            assert_eq!(largest, &sorted[len - k.min(len)..]);
+        }
+    }
+
+    #[test]
+    fn fuzz_range() {
+        let mut rng = thread_rng();
+        for len in 0..100 {
+            let ih: IntervalHeap<u32> = (0..len).map(|_| rng.gen_range(0, 50)).collect();
+            let lo = rng.gen_range(0, 50);
+            let hi = rng.gen_range(lo, 50);
+            let mut items: Vec<u32> = ih.range(lo..hi).cloned().collect();
+            items.sort();
+            let mut expected: Vec<u32> = ih.iter().cloned().filter(|&x| lo <= x && x < hi)
+                                           .collect();
+            expected.sort();
+            assert_eq!(items, expected);
+            assert_eq!(ih.count_in_range(lo..=hi), ih.iter().filter(|&&x| lo <= x && x <= hi)
+                                                     .count());
+            assert_eq!(ih.count_in_range(..), len);
+        }
+    }
+
+    /// Compares strings, panicking once the countdown runs out.
+    struct PanickingCmp {
+        countdown: Cell<usize>,
+    }
+
+    impl Compare<String> for PanickingCmp {
+        fn compare(&self, a: &String, b: &String) -> Ordering {
+            let n = self.countdown.get();
+            if n == 0 { panic!("comparator panicked"); }
+            self.countdown.set(n - 1);
+            a.cmp(b)
+        }
+    }
+
+    #[test]
+    fn test_panicking_comparator() {
+        type Heap = IntervalHeap<String, PanickingCmp>;
+        let ops: &[fn(&mut Heap)] = &[
+            |h| h.push("new".to_string()),
+            |h| { h.pop_min(); },
+            |h| { h.pop_max(); },
+            |h| { h.set_pop_strategy(PopStrategy::BottomUp); h.pop_min(); },
+            |h| { h.set_pop_strategy(PopStrategy::BottomUp); h.pop_max(); },
+            |h| { h.replace_min("new".to_string()); },
+            |h| { h.replace_max("new".to_string()); },
+            |h| { h.remove_where(|s| s == "item 017"); },
+            |h| { *h.peek_min_mut().unwrap() = "new".to_string(); },
+        ];
+        let mut rng = thread_rng();
+        let orig: Vec<String> = (0..50).map(|i| format!("item {:03}", i)).collect();
+        for op in ops {
+            for countdown in 0..30 {
+                let mut vec = orig.clone();
+                rng.shuffle(&mut vec);
+                let cmp = PanickingCmp { countdown: Cell::new(usize::MAX) };
+                let mut heap = IntervalHeap::from_vec_and_comparator(vec, cmp);
+                heap.cmp.countdown.set(countdown);
+                let before = heap.len();
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| op(&mut heap)));
+                // Every item is owned exactly once, and at most one item was
+                // pushed or popped.
+                let mut items = heap.into_vec();
+                let len = items.len();
+                assert!(len + 1 >= before && len <= before + 1);
+                items.sort();
+                items.dedup();
+                assert_eq!(items.len(), len);
+                assert!(items.iter().all(|s| orig.contains(s) || s == "new"));
+            }
+        }
+        for countdown in 0..100 {
+            let cmp = PanickingCmp { countdown: Cell::new(countdown) };
+            let vec = orig.iter().rev().cloned().collect();
+            let _ = panic::catch_unwind(|| IntervalHeap::from_vec_and_comparator(vec, cmp));
+        }
+    }
+
     #[test]
     fn test_from_vec() {
         let heap = IntervalHeap::<i32>::from(vec![]);
@@ -604,7 +2399,12 @@ mod test {
     #[test]
     fn test_is_valid() {
         fn new(data: Vec<i32>) -> IntervalHeap<i32> {
-            IntervalHeap { data: data, cmp: ::compare::natural() }
+            IntervalHeap {
+                data,
+                cmp: ::compare::natural(),
+                pop_strategy: Default::default(),
+                marker: PhantomData,
+            }
         }
 
         assert!(new(vec![]).is_valid());
@@ -0,0 +1,422 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An interval heap stored in a memory-mapped file, enabled by the `mmap` feature.
+
+use std::cmp;
+use std::fmt::{self, Debug};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::path::Path;
+use std::slice;
+
+use compare::{Compare, Natural};
+use memmap2::{MmapMut, MmapOptions};
+
+use super::{HeapStorage, IntervalHeap, Iter, heapify};
+use snapshot::{self, HEADER_LEN, Pod};
+
+/// Set in the snapshot header's flags while the items may have changed since the last flush.
+/// Snapshot readers ignore it.
+const FLAG_DIRTY: u32 = 2;
+
+const MIN_CAPACITY: usize = 16;
+
+/// A double-ended priority queue whose items are stored in a memory-mapped file.
+///
+/// The heap can grow larger than the memory available to the process, since the operating
+/// system pages its items in and out of the file as needed, and it persists across restarts
+/// of the process. The file has the layout of a [snapshot](
+/// struct.IntervalHeap.html#method.write_snapshot), followed by unused capacity; after a call
+/// to [`flush`](#method.flush), it can be read back with
+/// [`IntervalHeap::read_snapshot`](struct.IntervalHeap.html#method.read_snapshot).
+///
+/// Changes reach the file as the operating system writes the mapped pages back, at the
+/// latest when `flush` is called or the heap is dropped. If the process crashes while the
+/// heap is being changed, the item being moved may be lost or duplicated, and the heap is
+/// rebuilt when it is opened again. Use a
+/// [`DurableIntervalHeap`](struct.DurableIntervalHeap.html) if no change may be lost.
+///
+/// It is a logic error to open a file with a different ordering than the one it was
+/// written with, or to modify the file while it is open.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate interval_heap;
+/// # extern crate tempfile;
+/// # fn main() {
+/// use interval_heap::MmapIntervalHeap;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let path = dir.path().join("queue");
+/// {
+///     let mut heap = MmapIntervalHeap::<u64>::open(&path).unwrap();
+///     heap.push(3).unwrap();
+///     heap.push(1).unwrap();
+///     heap.push(4).unwrap();
+///     heap.flush().unwrap();
+/// }
+/// let mut heap = MmapIntervalHeap::<u64>::open(&path).unwrap();
+/// assert_eq!(heap.pop_min(), Some(1));
+/// assert_eq!(heap.pop_max(), Some(4));
+/// # }
+/// ```
+pub struct MmapIntervalHeap<T: Pod, C: Compare<T> = Natural<T>> {
+    heap: IntervalHeap<T, C, MmapStorage<T>>,
+}
+
+/// A memory-mapped file holding a snapshot header, followed by the items and unused
+/// capacity.
+///
+/// Growing the file may fail, so the storage does not grow on its own: pushing onto a full
+/// storage panics. Its owner calls `remap` to make room first.
+struct MmapStorage<T: Pod> {
+    file: File,
+    map: MmapMut,
+    len: usize,
+    /// Set once the flag is set in the header, before any item changes.
+    dirty: bool,
+    marker: PhantomData<T>,
+}
+
+/// Returns the items in the map.
+fn items<T: Pod>(map: &MmapMut, len: usize) -> &[T] {
+    assert!(HEADER_LEN + len * mem::size_of::<T>() <= map.len(), "items out of bounds");
+    // SAFETY: The map is page-aligned and large enough, `HEADER_LEN` is a multiple of the
+    // items' alignment, and every bit pattern is a valid `Pod` value.
+    unsafe { slice::from_raw_parts(map.as_ptr().add(HEADER_LEN) as *const T, len) }
+}
+
+/// Returns the items in the map for writing.
+fn items_mut<T: Pod>(map: &mut MmapMut, len: usize) -> &mut [T] {
+    assert!(HEADER_LEN + len * mem::size_of::<T>() <= map.len(), "items out of bounds");
+    // SAFETY: See `items`.
+    unsafe { slice::from_raw_parts_mut(map.as_mut_ptr().add(HEADER_LEN) as *mut T, len) }
+}
+
+/// Returns the flags in the header at the start of `bytes`.
+fn flags(bytes: &[u8]) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(&bytes[12..16]);
+    u32::from_le_bytes(buf)
+}
+
+impl<T: Pod> MmapStorage<T> {
+    /// Maps the given file, creating it if it does not exist yet.
+    fn open(path: &Path) -> io::Result<MmapStorage<T>> {
+        assert!(mem::size_of::<T>() != 0, "zero-sized items are not supported");
+        assert!(HEADER_LEN % mem::align_of::<T>() == 0, "item alignment is too large");
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false)
+                                     .open(path)?;
+        let file_len = file.metadata()?.len();
+        if file_len == 0 {
+            file.set_len((HEADER_LEN + MIN_CAPACITY * mem::size_of::<T>()) as u64)?;
+        } else if file_len < HEADER_LEN as u64 {
+            return Err(snapshot::invalid_data("file is too short"));
+        }
+        // SAFETY: The file must not be modified by others while it is mapped, as documented.
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+        let len = if file_len == 0 {
+            map[..HEADER_LEN].copy_from_slice(&snapshot::encode_header::<T>(0, 0));
+            0
+        } else {
+            let mut header = [0; HEADER_LEN];
+            header.copy_from_slice(&map[..HEADER_LEN]);
+            let (len, _) = snapshot::decode_header::<T>(&header)?;
+            if (map.len() - HEADER_LEN) / mem::size_of::<T>() < len {
+                return Err(snapshot::invalid_data("file is too short"));
+            }
+            len
+        };
+        let dirty = flags(&map) & FLAG_DIRTY != 0;
+        Ok(MmapStorage { file, map, len, dirty, marker: PhantomData })
+    }
+
+    /// Writes all changes to the file, making it a valid snapshot.
+    fn flush(&mut self) -> io::Result<()> {
+        let sum = snapshot::checksum(snapshot::as_bytes(self.as_slice()));
+        self.map[..HEADER_LEN].copy_from_slice(&snapshot::encode_header::<T>(self.len, sum));
+        self.map.flush()?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Marks the storage as dirty in the header. This must happen before any item changes.
+    fn mark_dirty(&mut self) {
+        if self.dirty { return; }
+        let flags = flags(&self.map) | FLAG_DIRTY;
+        self.map[12..16].copy_from_slice(&flags.to_le_bytes());
+        self.dirty = true;
+    }
+
+    /// Records the new number of items in the header, marking the storage as dirty.
+    fn set_len(&mut self, len: usize) {
+        self.mark_dirty();
+        self.map[16..24].copy_from_slice(&(len as u64).to_le_bytes());
+        self.len = len;
+    }
+
+    /// Resizes the file to hold `capacity` items and maps it again.
+    ///
+    /// The current map is only replaced once the new one is in place, so the storage is left
+    /// as it was if this fails. Both maps share the file's pages, so nothing has to be
+    /// written back first.
+    fn remap(&mut self, capacity: usize) -> io::Result<()> {
+        let new_len = HEADER_LEN + capacity * mem::size_of::<T>();
+        let map = if new_len > self.map.len() {
+            // Growing the file leaves the current map intact.
+            self.file.set_len(new_len as u64)?;
+            // SAFETY: See `open`.
+            unsafe { MmapOptions::new().len(new_len).map_mut(&self.file)? }
+        } else {
+            // The items that remain lie within both maps. Some platforms do not allow
+            // shrinking a mapped file, in which case this fails.
+            // SAFETY: See `open`.
+            let map = unsafe { MmapOptions::new().len(new_len).map_mut(&self.file)? };
+            self.file.set_len(new_len as u64)?;
+            map
+        };
+        self.map = map;
+        Ok(())
+    }
+}
+
+impl<T: Pod> HeapStorage<T> for MmapStorage<T> {
+    fn push(&mut self, item: T) {
+        let len = self.len;
+        assert!(len < self.capacity(), "storage is full");
+        // The item is written before it is counted, so that a crash cannot leave a stale
+        // item from the unused capacity in the heap.
+        items_mut(&mut self.map, len + 1)[len] = item;
+        self.set_len(len + 1);
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        let item = *self.as_slice().last()?;
+        self.set_len(self.len - 1);
+        Some(item)
+    }
+
+    fn swap_remove(&mut self, index: usize) -> T {
+        let len = self.len;
+        assert!(index < len, "index out of bounds");
+        let v = self.as_mut_slice();
+        let item = v[index];
+        v[index] = v[len - 1];
+        self.set_len(len - 1);
+        item
+    }
+
+    fn as_slice(&self) -> &[T] { items(&self.map, self.len) }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self.mark_dirty();
+        items_mut(&mut self.map, self.len)
+    }
+
+    fn capacity(&self) -> usize { (self.map.len() - HEADER_LEN) / mem::size_of::<T>() }
+
+    fn len(&self) -> usize { self.len }
+
+    fn clear(&mut self) { self.set_len(0); }
+}
+
+impl<T: Pod> Drop for MmapStorage<T> {
+    /// Flushes the storage if it has changed, so that opening it again does not have to
+    /// rebuild the heap. Errors are ignored.
+    fn drop(&mut self) {
+        if self.dirty { let _ = self.flush(); }
+    }
+}
+
+impl<T: Pod, C: Compare<T> + Default> MmapIntervalHeap<T, C> {
+    /// Opens the heap stored in the given file, creating the file if it does not exist yet.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<MmapIntervalHeap<T, C>> {
+        Self::open_with_comparator(path, C::default())
+    }
+}
+
+impl<T: Pod, C: Compare<T>> MmapIntervalHeap<T, C> {
+    /// Opens the heap stored in the given file, creating the file if it does not exist yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is zero-sized or needs an alignment greater than 32 bytes.
+    pub fn open_with_comparator<P: AsRef<Path>>(path: P, cmp: C)
+                                                -> io::Result<MmapIntervalHeap<T, C>> {
+        let mut storage = MmapStorage::open(path.as_ref())?;
+        if storage.dirty {
+            // The process changing the heap did not get to flush it, so the heap may have
+            // been caught in the middle of a change.
+            heapify(storage.as_mut_slice(), &cmp);
+        }
+        Ok(MmapIntervalHeap { heap: IntervalHeap::from_heap_storage(storage, cmp) })
+    }
+
+    /// Returns the number of items the heap can hold without growing its file.
+    pub fn capacity(&self) -> usize { self.heap.capacity() }
+
+    /// Returns the items in the heap in their internal order.
+    pub fn as_slice(&self) -> &[T] { self.heap.data.as_slice() }
+
+    /// Returns an iterator visiting all items in the heap in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, T> { self.heap.iter() }
+
+    /// Returns a reference to the smallest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn min(&self) -> Option<&T> { self.heap.min() }
+
+    /// Returns a reference to the greatest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn max(&self) -> Option<&T> { self.heap.max() }
+
+    /// Returns references to the smallest and greatest items in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn min_max(&self) -> Option<(&T, &T)> { self.heap.min_max() }
+
+    /// Pushes an item onto the heap, growing its file if necessary.
+    pub fn push(&mut self, item: T) -> io::Result<()> {
+        if self.heap.len() == self.heap.capacity() {
+            let capacity = cmp::max(self.heap.capacity() * 2, MIN_CAPACITY);
+            self.heap.data.remap(capacity)?;
+        }
+        self.heap.push(item);
+        Ok(())
+    }
+
+    /// Removes the smallest item from the heap and returns it.
+    ///
+    /// Returns `None` if the heap was empty.
+    pub fn pop_min(&mut self) -> Option<T> { self.heap.pop_min() }
+
+    /// Removes the greatest item from the heap and returns it.
+    ///
+    /// Returns `None` if the heap was empty.
+    pub fn pop_max(&mut self) -> Option<T> { self.heap.pop_max() }
+
+    /// Returns the number of items in the heap.
+    pub fn len(&self) -> usize { self.heap.len() }
+
+    /// Returns `true` if the heap contains no items.
+    pub fn is_empty(&self) -> bool { self.heap.is_empty() }
+
+    /// Removes all items from the heap.
+    ///
+    /// The file keeps its size; see [`shrink_to_fit`](#method.shrink_to_fit).
+    pub fn clear(&mut self) { self.heap.clear(); }
+
+    /// Shrinks the file to hold the items in the heap and no more.
+    pub fn shrink_to_fit(&mut self) -> io::Result<()> {
+        let capacity = cmp::max(self.heap.len(), MIN_CAPACITY);
+        if capacity < self.heap.capacity() { self.heap.data.remap(capacity) } else { Ok(()) }
+    }
+
+    /// Writes all changes to the file, making it a valid snapshot.
+    ///
+    /// This also happens when the heap is dropped, ignoring any errors.
+    pub fn flush(&mut self) -> io::Result<()> { self.heap.data.flush() }
+}
+
+impl<T: Pod + Debug, C: Compare<T>> Debug for MmapIntervalHeap<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+impl<'a, T: Pod, C: Compare<T>> IntoIterator for &'a MmapIntervalHeap<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Iter<'a, T> { self.iter() }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate tempfile;
+
+    use std::fs::{self, File};
+    use std::mem;
+    use rand::{thread_rng, Rng};
+    use super::super::IntervalHeap;
+    use super::MmapIntervalHeap;
+
+    #[test]
+    fn fuzz_reopen() {
+        let mut rng = thread_rng();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("heap");
+        let mut heap = MmapIntervalHeap::<u32>::open(&path).unwrap();
+        let mut model = IntervalHeap::new();
+        for _ in 0..2000 {
+            match rng.gen_range(0, 10) {
+                0..=4 => {
+                    let x = rng.gen_range(0, 100);
+                    heap.push(x).unwrap();
+                    model.push(x);
+                }
+                5 | 6 => assert_eq!(heap.pop_min(), model.pop_min()),
+                7 | 8 => assert_eq!(heap.pop_max(), model.pop_max()),
+                _ => {
+                    if rng.gen() { heap.flush().unwrap(); }
+                    if rng.gen() { heap.shrink_to_fit().unwrap(); }
+                    drop(heap);
+                    heap = MmapIntervalHeap::open(&path).unwrap();
+                }
+            }
+            assert_eq!(heap.as_slice(), &model.data[..]);
+        }
+        heap.flush().unwrap();
+        let snapshot = IntervalHeap::<u32>::read_snapshot(File::open(&path).unwrap()).unwrap();
+        assert_eq!(snapshot.data, model.data);
+    }
+
+    #[test]
+    fn test_drop_flushes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("heap");
+        {
+            let mut heap = MmapIntervalHeap::<u64>::open(&path).unwrap();
+            for x in 0..100 { heap.push(x * 37 % 100).unwrap(); }
+            heap.pop_min();
+        }
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(super::flags(&bytes) & super::FLAG_DIRTY, 0);
+        let heap = MmapIntervalHeap::<u64>::open(&path).unwrap();
+        assert!(!heap.heap.data.dirty);
+        assert_eq!(heap.len(), 99);
+        drop(heap);
+        let snapshot = IntervalHeap::<u64>::read_snapshot(File::open(&path).unwrap()).unwrap();
+        assert_eq!(snapshot.into_sorted_vec(), (1..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_dirty_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("heap");
+        {
+            let mut heap = MmapIntervalHeap::<u64>::open(&path).unwrap();
+            for x in 0..100 { heap.push(x).unwrap(); }
+            // Simulate a crash halfway through a change.
+            let storage = &mut heap.heap.data;
+            super::items_mut::<u64>(&mut storage.map, storage.len).reverse();
+            mem::forget(heap);
+        }
+        let heap = MmapIntervalHeap::<u64>::open(&path).unwrap();
+        let mut check = IntervalHeap::new();
+        check.data = heap.as_slice().to_vec();
+        assert!(check.is_valid());
+        assert_eq!(check.into_sorted_vec(), (0..100).collect::<Vec<_>>());
+    }
+}
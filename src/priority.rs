@@ -0,0 +1,331 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A double-ended priority queue of keys whose priorities can be looked up and changed.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::fmt::{self, Debug};
+use std::hash::{BuildHasher, Hash};
+use std::mem;
+
+use compare::{Compare, Natural, natural};
+use hashbrown::HashTable;
+
+use super::{AddressableIntervalHeap, Handle, Iter};
+
+/// Compares `(priority, key)` pairs by their priorities only.
+#[derive(Clone)]
+struct ByPriority<C>(C);
+
+impl<P, K, C: Compare<P>> Compare<(P, K)> for ByPriority<C> {
+    fn compare(&self, a: &(P, K), b: &(P, K)) -> Ordering { self.0.compare(&a.0, &b.0) }
+}
+
+/// A double-ended priority queue mapping each key to a priority.
+///
+/// The queue holds at most one priority per key. Priorities can be looked up by key in `O(1)`
+/// time, and changed or removed by key in `O(log n)` time. Each key is stored only once, next
+/// to its priority; the queue looks keys up through a hash table of handles to them.
+///
+/// It is a logic error for a priority to be modified in such a way that its ordering
+/// relative to any other priority, as determined by the queue's comparator, changes while it
+/// is in the queue, or for a key to be modified in such a way that its hash or equality
+/// changes. This is normally only possible through `Cell`, `RefCell`, global state, I/O, or
+/// unsafe code.
+///
+/// # Examples
+///
+/// ```
+/// use interval_heap::IntervalPriorityQueue;
+///
+/// let mut queue = IntervalPriorityQueue::new();
+/// queue.push("render", 3);
+/// queue.push("upload", 1);
+/// queue.push("index", 5);
+/// assert_eq!(queue.change_priority("upload", 8), Ok(1));
+/// assert_eq!(queue.get_priority("upload"), Some(&8));
+/// assert_eq!(queue.pop_max(), Some(("upload", 8)));
+/// assert_eq!(queue.pop_min(), Some(("render", 3)));
+/// ```
+#[derive(Clone)]
+pub struct IntervalPriorityQueue<K: Hash + Eq, P, C: Compare<P> = Natural<P>> {
+    heap: Heap<K, P, C>,
+    /// The handles of all items in the heap, hashed by their keys.
+    index: HashTable<Handle>,
+    hasher: RandomState,
+}
+
+type Heap<K, P, C> = AddressableIntervalHeap<(P, K), ByPriority<C>>;
+
+/// Returns the key of the item referred to by a handle from the index.
+fn key_at<K, P, C: Compare<P>>(heap: &Heap<K, P, C>, h: Handle) -> &K {
+    match heap.get(h) {
+        Some((_, k)) => k,
+        None => unreachable!("the index only holds handles of items in the heap"),
+    }
+}
+
+impl<K: Hash + Eq, P, C: Compare<P> + Default> Default for IntervalPriorityQueue<K, P, C> {
+    #[inline]
+    fn default() -> IntervalPriorityQueue<K, P, C> {
+        Self::with_comparator(C::default())
+    }
+}
+
+impl<K: Hash + Eq, P: Ord> IntervalPriorityQueue<K, P> {
+    /// Returns an empty queue ordered according to the natural order of its priorities.
+    pub fn new() -> IntervalPriorityQueue<K, P> { Self::with_comparator(natural()) }
+}
+
+impl<K: Hash + Eq, P, C: Compare<P>> IntervalPriorityQueue<K, P, C> {
+    /// Returns an empty queue whose priorities are ordered according to the given
+    /// comparator.
+    pub fn with_comparator(cmp: C) -> IntervalPriorityQueue<K, P, C> {
+        IntervalPriorityQueue {
+            heap: AddressableIntervalHeap::with_comparator(ByPriority(cmp)),
+            index: HashTable::new(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Returns an iterator visiting all keys and their priorities in arbitrary order.
+    pub fn iter(&self) -> PriorityIter<'_, K, P> { PriorityIter(self.heap.iter()) }
+
+    /// Returns the key with the smallest priority and that priority.
+    ///
+    /// Returns `None` if the queue is empty.
+    pub fn min(&self) -> Option<(&K, &P)> { self.heap.min().map(|(p, k)| (k, p)) }
+
+    /// Returns the key with the greatest priority and that priority.
+    ///
+    /// Returns `None` if the queue is empty.
+    pub fn max(&self) -> Option<(&K, &P)> { self.heap.max().map(|(p, k)| (k, p)) }
+
+    /// Returns `true` if the queue contains the given key.
+    pub fn contains_key<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> bool where K: Borrow<Q> {
+        self.find(key).is_some()
+    }
+
+    /// Returns a reference to the priority of the given key.
+    ///
+    /// Returns `None` if the key is not in the queue.
+    pub fn get_priority<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> Option<&P>
+            where K: Borrow<Q> {
+        self.find(key).and_then(|h| self.heap.get(h)).map(|(p, _)| p)
+    }
+
+    /// Pushes a key with the given priority onto the queue.
+    ///
+    /// If the key is already in the queue, its priority is replaced and the old priority is
+    /// returned. The key in the queue is kept and `key` is dropped.
+    pub fn push(&mut self, key: K, prio: P) -> Option<P> {
+        if let Some(h) = self.find(&key) {
+            return Some(self.replace_priority(h, prio));
+        }
+        let hash = self.hasher.hash_one(&key);
+        let h = self.heap.push((prio, key));
+        let (heap, hasher) = (&self.heap, &self.hasher);
+        self.index.insert_unique(hash, h, |&h| hasher.hash_one(key_at(heap, h)));
+        None
+    }
+
+    /// Changes the priority of the given key, moving the key to its new place in the queue.
+    ///
+    /// Returns the old priority, or gives `prio` back as an error if the key is not in the
+    /// queue.
+    pub fn change_priority<Q: ?Sized + Hash + Eq>(&mut self, key: &Q, prio: P) -> Result<P, P>
+            where K: Borrow<Q> {
+        match self.find(key) {
+            Some(h) => Ok(self.replace_priority(h, prio)),
+            None => Err(prio),
+        }
+    }
+
+    /// Removes the given key from the queue and returns it along with its priority.
+    ///
+    /// Returns `None` if the key is not in the queue.
+    pub fn remove<Q: ?Sized + Hash + Eq>(&mut self, key: &Q) -> Option<(K, P)>
+            where K: Borrow<Q> {
+        let h = self.find(key)?;
+        Some(self.remove_handle(h))
+    }
+
+    /// Removes the key with the smallest priority from the queue and returns it along with
+    /// its priority.
+    ///
+    /// Returns `None` if the queue was empty.
+    pub fn pop_min(&mut self) -> Option<(K, P)> {
+        let h = self.heap.min_handle()?;
+        Some(self.remove_handle(h))
+    }
+
+    /// Removes the key with the greatest priority from the queue and returns it along with
+    /// its priority.
+    ///
+    /// Returns `None` if the queue was empty.
+    pub fn pop_max(&mut self) -> Option<(K, P)> {
+        let h = self.heap.max_handle()?;
+        Some(self.remove_handle(h))
+    }
+
+    /// Returns the number of keys in the queue.
+    pub fn len(&self) -> usize { self.heap.len() }
+
+    /// Returns `true` if the queue contains no keys.
+    pub fn is_empty(&self) -> bool { self.heap.is_empty() }
+
+    /// Removes all keys from the queue.
+    pub fn clear(&mut self) {
+        self.heap.clear();
+        self.index.clear();
+    }
+
+    /// Returns the handle of the given key's item.
+    fn find<Q: ?Sized + Hash + Eq>(&self, key: &Q) -> Option<Handle> where K: Borrow<Q> {
+        let hash = self.hasher.hash_one(key);
+        self.index.find(hash, |&h| key_at(&self.heap, h).borrow() == key).copied()
+    }
+
+    /// Replaces the priority of the item referred to by a handle from the index.
+    fn replace_priority(&mut self, h: Handle, prio: P) -> P {
+        match self.heap.update_with(h, |item| mem::replace(&mut item.0, prio)) {
+            Some(old) => old,
+            None => unreachable!("the index only holds handles of items in the heap"),
+        }
+    }
+
+    /// Removes the item referred to by a handle from the index from both the heap and the
+    /// index.
+    fn remove_handle(&mut self, h: Handle) -> (K, P) {
+        let (p, k) = match self.heap.remove(h) {
+            Some(item) => item,
+            None => unreachable!("the index only holds handles of items in the heap"),
+        };
+        let hash = self.hasher.hash_one(&k);
+        match self.index.find_entry(hash, |&other| other == h) {
+            Ok(entry) => { entry.remove(); }
+            Err(_) => unreachable!("every item in the heap has its handle in the index"),
+        }
+        (k, p)
+    }
+
+    /// Checks if every key in the heap is indexed by its handle.
+    #[cfg(test)]
+    fn is_consistent(&self) -> bool {
+        self.index.len() == self.heap.len() &&
+        self.index.iter().all(|&h| {
+            self.heap.get(h).is_some_and(|(_, k)| self.find(k) == Some(h))
+        })
+    }
+}
+
+impl<K: Hash + Eq + Debug, P: Debug, C: Compare<P>> Debug for IntervalPriorityQueue<K, P, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(PriorityIter(self.heap.iter())).finish()
+    }
+}
+
+impl<'a, K: Hash + Eq, P, C: Compare<P>> IntoIterator
+        for &'a IntervalPriorityQueue<K, P, C> {
+    type Item = (&'a K, &'a P);
+    type IntoIter = PriorityIter<'a, K, P>;
+    fn into_iter(self) -> PriorityIter<'a, K, P> { self.iter() }
+}
+
+/// An iterator over the keys and priorities of an `IntervalPriorityQueue` in arbitrary order.
+///
+/// Acquire through [`IntervalPriorityQueue::iter`](
+/// struct.IntervalPriorityQueue.html#method.iter).
+pub struct PriorityIter<'a, K: 'a, P: 'a>(Iter<'a, (P, K)>);
+
+impl<'a, K, P> Clone for PriorityIter<'a, K, P> {
+    fn clone(&self) -> PriorityIter<'a, K, P> { PriorityIter(self.0.clone()) }
+}
+
+impl<'a, K, P> Iterator for PriorityIter<'a, K, P> {
+    type Item = (&'a K, &'a P);
+    #[inline] fn next(&mut self) -> Option<(&'a K, &'a P)> { self.0.next().map(|(p, k)| (k, p)) }
+    #[inline] fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
+}
+
+impl<'a, K, P> DoubleEndedIterator for PriorityIter<'a, K, P> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a P)> {
+        self.0.next_back().map(|(p, k)| (k, p))
+    }
+}
+
+impl<'a, K, P> ExactSizeIterator for PriorityIter<'a, K, P> {}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::mem;
+    use rand::{thread_rng, Rng};
+    use super::IntervalPriorityQueue;
+
+    #[test]
+    fn fuzz_priorities() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let mut queue = IntervalPriorityQueue::new();
+            let mut model = HashMap::new();
+            for _ in 0..200 {
+                let key: u32 = rng.gen_range(0, 30);
+                let prio: u32 = rng.gen_range(0, 100);
+                match rng.gen_range(0, 5) {
+                    0 => assert_eq!(queue.push(key, prio), model.insert(key, prio)),
+                    1 => {
+                        let expected = match model.get_mut(&key) {
+                            Some(p) => Ok(mem::replace(p, prio)),
+                            None => Err(prio),
+                        };
+                        assert_eq!(queue.change_priority(&key, prio), expected);
+                    }
+                    2 => assert_eq!(queue.remove(&key), model.remove(&key).map(|p| (key, p))),
+                    3 => {
+                        let min = model.values().min().cloned();
+                        let popped = queue.pop_min();
+                        assert_eq!(popped.map(|(_, p)| p), min);
+                        if let Some((k, p)) = popped { assert_eq!(model.remove(&k), Some(p)); }
+                    }
+                    _ => {
+                        let max = model.values().max().cloned();
+                        let popped = queue.pop_max();
+                        assert_eq!(popped.map(|(_, p)| p), max);
+                        if let Some((k, p)) = popped { assert_eq!(model.remove(&k), Some(p)); }
+                    }
+                }
+                assert!(queue.is_consistent());
+                assert_eq!(queue.len(), model.len());
+                for (k, p) in &model {
+                    assert_eq!(queue.get_priority(k), Some(p));
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_keys_without_clone() {
+        #[derive(PartialEq, Eq, Hash, Debug)]
+        struct Key(String);
+
+        let mut queue = IntervalPriorityQueue::new();
+        assert_eq!(queue.push(Key("a".to_string()), 2), None);
+        assert_eq!(queue.push(Key("b".to_string()), 1), None);
+        assert_eq!(queue.push(Key("a".to_string()), 3), Some(2));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.change_priority(&Key("b".to_string()), 4), Ok(1));
+        assert_eq!(queue.pop_min(), Some((Key("a".to_string()), 3)));
+        assert!(queue.is_consistent());
+        assert_eq!(queue.remove(&Key("b".to_string())), Some((Key("b".to_string()), 4)));
+        assert!(queue.is_empty());
+    }
+}
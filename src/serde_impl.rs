@@ -0,0 +1,86 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! `Serialize` and `Deserialize` implementations, enabled by the `serde` feature.
+//!
+//! A heap is serialized as the sequence of its items in their internal order, so a heap
+//! that is deserialized again gets the same layout without being rebuilt. A sequence that
+//! was not produced by a heap with the same ordering is turned into a valid heap in `O(n)`
+//! time.
+
+use compare::Compare;
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use super::{HeapStorage, IntervalHeap, heapify};
+
+impl<T: Serialize, C: Compare<T>, H: HeapStorage<T>> Serialize for IntervalHeap<T, C, H> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.data.as_slice())
+    }
+}
+
+impl<'de, T: Deserialize<'de>, C: Compare<T> + Default> Deserialize<'de> for IntervalHeap<T, C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<IntervalHeap<T, C>, D::Error> {
+        let mut heap = IntervalHeap::with_comparator(C::default());
+        heap.data = Vec::deserialize(deserializer)?;
+        if !heap.is_valid() {
+            heapify(&mut heap.data, &heap.cmp);
+        }
+        debug_assert!(heap.is_valid());
+        Ok(heap)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate bincode;
+    extern crate serde_json;
+
+    use compare::{Natural, Rev};
+    use rand::{thread_rng, Rng};
+    use super::super::IntervalHeap;
+
+    #[test]
+    fn fuzz_round_trip() {
+        let mut rng = thread_rng();
+        for len in 0..50 {
+            let vec: Vec<u32> = (0..len).map(|_| rng.gen_range(0, 100)).collect();
+            let heap = IntervalHeap::from(vec);
+
+            let json = serde_json::to_string(&heap).unwrap();
+            let from_json: IntervalHeap<u32> = serde_json::from_str(&json).unwrap();
+            assert_eq!(from_json.data, heap.data);
+
+            let bytes = bincode::serialize(&heap).unwrap();
+            let from_bincode: IntervalHeap<u32> = bincode::deserialize(&bytes).unwrap();
+            assert_eq!(from_bincode.data, heap.data);
+        }
+    }
+
+    #[test]
+    fn fuzz_deserialize_invalid() {
+        let mut rng = thread_rng();
+        for len in 0..50 {
+            let mut vec: Vec<u32> = (0..len).map(|_| rng.gen_range(0, 100)).collect();
+            let json = serde_json::to_string(&vec).unwrap();
+            let heap: IntervalHeap<u32, Rev<Natural<u32>>> = serde_json::from_str(&json).unwrap();
+            assert!(heap.is_valid());
+            vec.sort_by(|a, b| b.cmp(a));
+            assert_eq!(heap.into_sorted_vec(), vec);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_error() {
+        assert!(serde_json::from_str::<IntervalHeap<u32>>("[1, -2]").is_err());
+        assert!(serde_json::from_str::<IntervalHeap<u32>>("{}").is_err());
+    }
+}
@@ -0,0 +1,335 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A compact binary snapshot format for heaps of plain old data.
+
+use std::cmp;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::slice;
+
+use compare::Compare;
+
+use super::{HeapStorage, IntervalHeap, heapify};
+
+const MAGIC: [u8; 4] = *b"IVHP";
+const VERSION: u32 = 1;
+const FLAG_BIG_ENDIAN: u32 = 1;
+pub(crate) const HEADER_LEN: usize = 32;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Types that can be written to and read from snapshots as raw bytes.
+///
+/// # Safety
+///
+/// Implementors must be `Copy` types without padding bytes, pointers or references, for
+/// which every bit pattern (in particular, all zeros) is a valid value.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($t:ty)*) => { $(unsafe impl Pod for $t {})* }
+}
+
+impl_pod!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize f32 f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// Returns the items' bytes.
+pub(crate) fn as_bytes<T: Pod>(items: &[T]) -> &[u8] {
+    // SAFETY: `Pod` types have no padding, so all their bytes are initialized.
+    unsafe { slice::from_raw_parts(items.as_ptr() as *const u8, mem::size_of_val(items)) }
+}
+
+/// Returns the items' bytes for writing.
+fn as_bytes_mut<T: Pod>(items: &mut [T]) -> &mut [u8] {
+    // SAFETY: As in `as_bytes`, and every bit pattern is a valid `Pod` value.
+    unsafe { slice::from_raw_parts_mut(items.as_mut_ptr() as *mut u8, mem::size_of_val(items)) }
+}
+
+/// Computes the snapshot checksum of the given bytes.
+pub(crate) fn checksum(bytes: &[u8]) -> u64 { update_checksum(FNV_OFFSET, bytes) }
+
+/// Continues computing a checksum with the given bytes. Only the last bytes of a checksum
+/// may be passed in a length that is not a multiple of eight.
+fn update_checksum(mut hash: u64, bytes: &[u8]) -> u64 {
+    let mut words = bytes.chunks_exact(8);
+    for word in &mut words {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(word);
+        hash = (hash ^ u64::from_le_bytes(buf)).wrapping_mul(FNV_PRIME);
+    }
+    let rest = words.remainder();
+    if !rest.is_empty() {
+        let mut buf = [0; 8];
+        buf[..rest.len()].copy_from_slice(rest);
+        hash = (hash ^ u64::from_le_bytes(buf)).wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+pub(crate) fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Encodes the header of a snapshot of `len` items of type `T` with the given checksum.
+///
+/// Panics if `T` is zero-sized.
+pub(crate) fn encode_header<T: Pod>(len: usize, checksum: u64) -> [u8; HEADER_LEN] {
+    assert!(mem::size_of::<T>() != 0, "zero-sized items are not supported");
+    let flags = if cfg!(target_endian = "big") { FLAG_BIG_ENDIAN } else { 0 };
+    let mut header = [0; HEADER_LEN];
+    header[0..4].copy_from_slice(&MAGIC);
+    header[4..8].copy_from_slice(&VERSION.to_le_bytes());
+    header[8..12].copy_from_slice(&(mem::size_of::<T>() as u32).to_le_bytes());
+    header[12..16].copy_from_slice(&flags.to_le_bytes());
+    header[16..24].copy_from_slice(&(len as u64).to_le_bytes());
+    header[24..32].copy_from_slice(&checksum.to_le_bytes());
+    header
+}
+
+/// Decodes the header of a snapshot of items of type `T`, returning the number of items and
+/// their checksum.
+///
+/// Panics if `T` is zero-sized, as the header's length could otherwise claim any number of
+/// items without a single byte backing them.
+pub(crate) fn decode_header<T: Pod>(header: &[u8; HEADER_LEN]) -> io::Result<(usize, u64)> {
+    assert!(mem::size_of::<T>() != 0, "zero-sized items are not supported");
+    let field = |at: usize| {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(&header[at..at + 4]);
+        u32::from_le_bytes(buf)
+    };
+    let wide_field = |at: usize| {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(&header[at..at + 8]);
+        u64::from_le_bytes(buf)
+    };
+    if header[0..4] != MAGIC { return Err(invalid_data("not an interval heap snapshot")); }
+    if field(4) != VERSION { return Err(invalid_data("unsupported snapshot version")); }
+    if field(8) as usize != mem::size_of::<T>() {
+        return Err(invalid_data("snapshot item size does not match"));
+    }
+    let big_endian = field(12) & FLAG_BIG_ENDIAN != 0;
+    if big_endian != cfg!(target_endian = "big") {
+        return Err(invalid_data("snapshot was written with a different byte order"));
+    }
+    let len = wide_field(16);
+    if len > usize::MAX as u64 || (len as usize).checked_mul(mem::size_of::<T>()).is_none() {
+        return Err(invalid_data("snapshot is too large"));
+    }
+    Ok((len as usize, wide_field(24)))
+}
+
+/// The number of bytes `read_items` reads at a time, roughly.
+const READ_LEN: usize = 64 << 10;
+
+/// The number of bytes `read_items` allocates ahead of the items it has read, at most.
+const ALLOC_LEN: usize = 64 << 20;
+
+/// Reads the items of a snapshot, checking the header and the checksum.
+///
+/// The items are read straight into the vector returned, about 64 KiB at a time. The
+/// header's length is not trusted for allocating: the vector grows along with the items
+/// read, so a reader that provides fewer items than the header claims cannot make it
+/// allocate more than twice the memory of the items provided, or 64 MiB if that is more.
+pub(crate) fn read_items<T: Pod, R: Read>(mut reader: R) -> io::Result<Vec<T>> {
+    let mut header = [0; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    let (len, sum) = decode_header::<T>(&header)?;
+    let size = mem::size_of::<T>();
+    // Reading a multiple of eight items at a time keeps the checksum's words aligned.
+    let batch = 8 * cmp::max(READ_LEN / (8 * size), 1);
+    let mut items: Vec<T> = vec![];
+    let mut hash = FNV_OFFSET;
+    while items.len() < len {
+        let start = items.len();
+        let end = cmp::min(len, start + batch);
+        if end > items.capacity() {
+            // Grow by the items read so far, or by `ALLOC_LEN` if that is more, but not
+            // beyond `len`, so the vector ends up with exactly the capacity needed.
+            let grow = cmp::max(start, cmp::max(ALLOC_LEN / size, batch));
+            items.reserve_exact(cmp::min(len - start, grow));
+        }
+        // SAFETY: All zeros is a valid `Pod` value.
+        items.resize(end, unsafe { mem::zeroed() });
+        let bytes = as_bytes_mut(&mut items[start..]);
+        match reader.read_exact(bytes) {
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Err(invalid_data("snapshot is truncated"));
+            }
+            res => res?,
+        }
+        hash = update_checksum(hash, bytes);
+    }
+    if hash != sum { return Err(invalid_data("snapshot checksum does not match")); }
+    Ok(items)
+}
+
+impl<T: Pod, C: Compare<T>, S: HeapStorage<T>> IntervalHeap<T, C, S> {
+    /// Writes a snapshot of the heap to `writer`.
+    ///
+    /// The snapshot contains the heap's items as raw bytes in their internal order, so
+    /// reading it back with [`read_snapshot`](#method.read_snapshot) restores the heap
+    /// without rebuilding it. It consists of a 32-byte header followed by the items:
+    ///
+    /// | offset | size | content                                                |
+    /// |--------|------|--------------------------------------------------------|
+    /// | 0      | 4    | magic bytes `IVHP`                                     |
+    /// | 4      | 4    | format version                                         |
+    /// | 8      | 4    | size of an item in bytes                               |
+    /// | 12     | 4    | flags (bit 0 is set if the items are big-endian)       |
+    /// | 16     | 8    | number of items                                        |
+    /// | 24     | 8    | checksum of the items' bytes                           |
+    ///
+    /// All header fields are little-endian. The checksum is a 64-bit FNV-1a hash over the
+    /// items' bytes, taken eight bytes at a time as little-endian words, with the last few
+    /// bytes zero-padded to a full word.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is zero-sized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::IntervalHeap;
+    ///
+    /// let heap = IntervalHeap::from(vec![5u32, 1, 8, 3]);
+    /// let mut bytes = vec![];
+    /// heap.write_snapshot(&mut bytes).unwrap();
+    ///
+    /// let restored = IntervalHeap::<u32>::read_snapshot(&bytes[..]).unwrap();
+    /// assert_eq!(restored.into_sorted_vec(), [1, 3, 5, 8]);
+    /// ```
+    pub fn write_snapshot<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let bytes = as_bytes(self.data.as_slice());
+        writer.write_all(&encode_header::<T>(self.len(), checksum(bytes)))?;
+        writer.write_all(bytes)
+    }
+}
+
+impl<T: Pod, C: Compare<T>> IntervalHeap<T, C> {
+    /// Reads a heap from a snapshot written by [`write_snapshot`](#method.write_snapshot).
+    ///
+    /// A snapshot written by a heap with the same ordering is restored without comparing any
+    /// items beyond checking that they are in heap order. Otherwise, the items are rebuilt
+    /// into a valid heap in `O(n)` time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is zero-sized.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `ErrorKind::InvalidData` if the snapshot's header does not match the
+    /// item type or the items do not match the checksum, and with any error `reader`
+    /// returns.
+    pub fn read_snapshot<R: Read>(reader: R) -> io::Result<IntervalHeap<T, C>>
+            where C: Default {
+        let mut heap = IntervalHeap::with_comparator(C::default());
+        heap.data = read_items(reader)?;
+        if !heap.is_valid() {
+            heapify(&mut heap.data, &heap.cmp);
+        }
+        debug_assert!(heap.is_valid());
+        Ok(heap)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::ErrorKind;
+    use compare::{Natural, Rev};
+    use rand::{thread_rng, Rng};
+    use super::super::IntervalHeap;
+    use super::HEADER_LEN;
+
+    #[test]
+    fn fuzz_round_trip() {
+        let mut rng = thread_rng();
+        for len in 0..50 {
+            let vec: Vec<[u16; 3]> = (0..len).map(|_| [rng.gen(), rng.gen(), rng.gen()]).collect();
+            let heap = IntervalHeap::from(vec);
+            let mut bytes = vec![];
+            heap.write_snapshot(&mut bytes).unwrap();
+            assert_eq!(bytes.len(), HEADER_LEN + len * 6);
+            let restored = IntervalHeap::<[u16; 3]>::read_snapshot(&bytes[..]).unwrap();
+            assert_eq!(restored.data, heap.data);
+        }
+    }
+
+    #[test]
+    fn test_large_round_trip() {
+        let heap: IntervalHeap<u32> = (0..100_000).map(|x| x * 7 % 100_003).collect();
+        let mut bytes = vec![];
+        heap.write_snapshot(&mut bytes).unwrap();
+        let restored = IntervalHeap::<u32>::read_snapshot(&bytes[..]).unwrap();
+        assert_eq!(restored.data, heap.data);
+        assert_eq!(restored.data.capacity(), heap.len());
+
+        let mut corrupted = bytes.clone();
+        corrupted[bytes.len() - 1] ^= 1;
+        assert!(IntervalHeap::<u32>::read_snapshot(&corrupted[..]).is_err());
+        assert!(IntervalHeap::<u32>::read_snapshot(&bytes[..bytes.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn fuzz_read_other_ordering() {
+        let mut rng = thread_rng();
+        for len in 0..50 {
+            let mut vec: Vec<u32> = (0..len).map(|_| rng.gen_range(0, 100)).collect();
+            let mut bytes = vec![];
+            IntervalHeap::from(vec.clone()).write_snapshot(&mut bytes).unwrap();
+            let heap = IntervalHeap::<u32, Rev<Natural<u32>>>::read_snapshot(&bytes[..]).unwrap();
+            assert!(heap.is_valid());
+            vec.sort_by(|a, b| b.cmp(a));
+            assert_eq!(heap.into_sorted_vec(), vec);
+        }
+    }
+
+    #[test]
+    fn test_invalid_snapshot() {
+        let heap = IntervalHeap::from(vec![5u32, 1, 8, 3]);
+        let mut bytes = vec![];
+        heap.write_snapshot(&mut bytes).unwrap();
+        let read = |bytes: &[u8]| IntervalHeap::<u32>::read_snapshot(bytes).unwrap_err().kind();
+
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER_LEN + 2] ^= 1;
+        assert_eq!(read(&corrupted), ErrorKind::InvalidData);
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(read(&bad_magic), ErrorKind::InvalidData);
+
+        assert_eq!(read(&bytes[..bytes.len() - 1]), ErrorKind::InvalidData);
+        assert_eq!(read(&bytes[..HEADER_LEN - 1]), ErrorKind::UnexpectedEof);
+
+        // A huge length in the header must not be trusted for allocating.
+        let mut huge = bytes.clone();
+        huge[16..24].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        assert_eq!(read(&huge), ErrorKind::InvalidData);
+        huge[16..24].copy_from_slice(&(1u64 << 61).to_le_bytes());
+        assert_eq!(read(&huge), ErrorKind::InvalidData);
+        assert_eq!(IntervalHeap::<u64>::read_snapshot(&bytes[..]).unwrap_err().kind(),
+                   ErrorKind::InvalidData);
+    }
+    #[test]
+    #[should_panic(expected = "zero-sized items are not supported")]
+    fn test_zero_sized_items() {
+        let mut header = [0; HEADER_LEN];
+        header[0..4].copy_from_slice(b"IVHP");
+        header[4..8].copy_from_slice(&1u32.to_le_bytes());
+        header[16..24].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        let _ = IntervalHeap::<[u8; 0]>::read_snapshot(&header[..]);
+    }
+}
@@ -0,0 +1,276 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An interval heap that pops equal items in a predictable order.
+
+use std::cmp::Ordering;
+use std::fmt::{self, Debug};
+use std::slice;
+
+use compare::{Compare, Natural, natural};
+
+use super::IntervalHeap;
+
+/// Determines at which end a `StableIntervalHeap` pops equal items in the order they were
+/// pushed.
+///
+/// The other end pops equal items in the reverse order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FifoEnd {
+    /// `pop_min` pops equal items first-in-first-out and `pop_max` last-in-first-out.
+    Min,
+    /// `pop_max` pops equal items first-in-first-out and `pop_min` last-in-first-out.
+    Max,
+}
+
+/// An item along with the sequence number it was pushed with.
+#[derive(Clone)]
+struct Seq<T> {
+    seq: u64,
+    item: T,
+}
+
+/// Breaks ties between equal items by their sequence numbers.
+#[derive(Clone)]
+struct Stable<C> {
+    cmp: C,
+    fifo_end: FifoEnd,
+}
+
+impl<T, C: Compare<T>> Compare<Seq<T>> for Stable<C> {
+    fn compare(&self, a: &Seq<T>, b: &Seq<T>) -> Ordering {
+        self.cmp.compare(&a.item, &b.item).then_with(|| match self.fifo_end {
+            FifoEnd::Min => a.seq.cmp(&b.seq),
+            FifoEnd::Max => b.seq.cmp(&a.seq),
+        })
+    }
+}
+
+/// A double-ended priority queue that pops items comparing equal in a predictable order.
+///
+/// Every pushed item is tagged with a sequence number, which decides between items that
+/// compare equal: depending on the heap's [`FifoEnd`](enum.FifoEnd.html), one end pops them in
+/// the order they were pushed and the other end in the reverse order.
+///
+/// Should the sequence numbers ever run out, the items in the heap are renumbered without
+/// changing their relative order.
+///
+/// It is a logic error for an item to be modified in such a way that the
+/// item's ordering relative to any other item, as determined by the heap's
+/// comparator, changes while it is in the heap. This is normally only
+/// possible through `Cell`, `RefCell`, global state, I/O, or unsafe code.
+#[derive(Clone)]
+pub struct StableIntervalHeap<T, C: Compare<T> = Natural<T>> {
+    heap: IntervalHeap<Seq<T>, Stable<C>>,
+    next_seq: u64,
+}
+
+impl<T: Ord> StableIntervalHeap<T> {
+    /// Returns an empty heap ordered according to the natural order of its items.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::{FifoEnd, StableIntervalHeap};
+    ///
+    /// let mut heap = StableIntervalHeap::new(FifoEnd::Min);
+    /// heap.push((1, "first"));
+    /// heap.push((1, "second"));
+    /// heap.push((1, "third"));
+    /// assert_eq!(heap.pop_min(), Some((1, "first")));
+    /// assert_eq!(heap.pop_max(), Some((1, "third")));
+    /// ```
+    pub fn new(fifo_end: FifoEnd) -> StableIntervalHeap<T> {
+        Self::with_comparator(fifo_end, natural())
+    }
+}
+
+impl<T, C: Compare<T>> StableIntervalHeap<T, C> {
+    /// Returns an empty heap ordered according to the given comparator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use interval_heap::{FifoEnd, FnComparator, StableIntervalHeap};
+    ///
+    /// let by_prio = FnComparator(|a: &(u32, &str), b: &(u32, &str)| a.0.cmp(&b.0));
+    /// let mut jobs = StableIntervalHeap::with_comparator(FifoEnd::Max, by_prio);
+    /// jobs.push((2, "a"));
+    /// jobs.push((2, "b"));
+    /// jobs.push((1, "c"));
+    /// assert_eq!(jobs.pop_max(), Some((2, "a")));
+    /// assert_eq!(jobs.pop_max(), Some((2, "b")));
+    /// ```
+    pub fn with_comparator(fifo_end: FifoEnd, cmp: C) -> StableIntervalHeap<T, C> {
+        StableIntervalHeap {
+            heap: IntervalHeap::with_comparator(Stable { cmp, fifo_end }),
+            next_seq: 0,
+        }
+    }
+
+    /// Returns the end at which the heap pops equal items first-in-first-out.
+    pub fn fifo_end(&self) -> FifoEnd { self.heap.cmp.fifo_end }
+
+    /// Returns an iterator visiting all items in the heap in arbitrary order.
+    pub fn iter(&self) -> StableIter<'_, T> { StableIter(self.heap.data.iter()) }
+
+    /// Returns a reference to the smallest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn min(&self) -> Option<&T> { self.heap.min().map(|s| &s.item) }
+
+    /// Returns a reference to the greatest item in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn max(&self) -> Option<&T> { self.heap.max().map(|s| &s.item) }
+
+    /// Returns references to the smallest and greatest items in the heap.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn min_max(&self) -> Option<(&T, &T)> {
+        self.heap.min_max().map(|(min, max)| (&min.item, &max.item))
+    }
+
+    /// Pushes an item onto the heap.
+    pub fn push(&mut self, item: T) {
+        if self.next_seq == u64::MAX { self.renumber(); }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.heap.push(Seq { seq, item });
+    }
+
+    /// Removes the smallest item from the heap and returns it.
+    ///
+    /// Returns `None` if the heap was empty.
+    pub fn pop_min(&mut self) -> Option<T> { self.heap.pop_min().map(|s| s.item) }
+
+    /// Removes the greatest item from the heap and returns it.
+    ///
+    /// Returns `None` if the heap was empty.
+    pub fn pop_max(&mut self) -> Option<T> { self.heap.pop_max().map(|s| s.item) }
+
+    /// Returns the number of items in the heap.
+    pub fn len(&self) -> usize { self.heap.len() }
+
+    /// Returns `true` if the heap contains no items.
+    pub fn is_empty(&self) -> bool { self.heap.is_empty() }
+
+    /// Removes all items from the heap.
+    pub fn clear(&mut self) {
+        self.heap.clear();
+        self.next_seq = 0;
+    }
+
+    /// Consumes the heap and returns its items as a vector in sorted (ascending) order.
+    ///
+    /// Equal items are in the order `pop_min` would have returned them.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        self.heap.into_sorted_vec().into_iter().map(|s| s.item).collect()
+    }
+
+    /// Assigns the sequence numbers `0..len` to the items in the heap, preserving their
+    /// order. Since the items' relative order is unchanged, so is the heap's layout.
+    fn renumber(&mut self) {
+        let data = &mut self.heap.data;
+        let mut order: Vec<usize> = (0..data.len()).collect();
+        order.sort_by_key(|&i| data[i].seq);
+        for (seq, i) in order.into_iter().enumerate() {
+            data[i].seq = seq as u64;
+        }
+        self.next_seq = data.len() as u64;
+    }
+}
+
+impl<T: Debug, C: Compare<T>> Debug for StableIntervalHeap<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T, C: Compare<T>> Extend<T> for StableIntervalHeap<T, C> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        let (lower, _) = iter.size_hint();
+        self.heap.reserve(lower);
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+impl<'a, T, C: Compare<T>> IntoIterator for &'a StableIntervalHeap<T, C> {
+    type Item = &'a T;
+    type IntoIter = StableIter<'a, T>;
+    fn into_iter(self) -> StableIter<'a, T> { self.iter() }
+}
+
+/// An iterator over a `StableIntervalHeap` in arbitrary order.
+///
+/// Acquire through [`StableIntervalHeap::iter`](struct.StableIntervalHeap.html#method.iter).
+pub struct StableIter<'a, T: 'a>(slice::Iter<'a, Seq<T>>);
+
+impl<'a, T> Clone for StableIter<'a, T> {
+    fn clone(&self) -> StableIter<'a, T> { StableIter(self.0.clone()) }
+}
+
+impl<'a, T> Iterator for StableIter<'a, T> {
+    type Item = &'a T;
+    #[inline] fn next(&mut self) -> Option<&'a T> { self.0.next().map(|s| &s.item) }
+    #[inline] fn size_hint(&self) -> (usize, Option<usize>) { self.0.size_hint() }
+}
+
+impl<'a, T> DoubleEndedIterator for StableIter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> { self.0.next_back().map(|s| &s.item) }
+}
+
+impl<'a, T> ExactSizeIterator for StableIter<'a, T> {}
+
+#[cfg(test)]
+mod test {
+    use rand::{thread_rng, Rng};
+    use super::super::FnComparator;
+    use super::{FifoEnd, StableIntervalHeap};
+
+    #[test]
+    fn fuzz_stable() {
+        let mut rng = thread_rng();
+        for &fifo_end in &[FifoEnd::Min, FifoEnd::Max] {
+            for len in 0..50 {
+                let by_prio = FnComparator(|a: &(u32, usize), b: &(u32, usize)| a.0.cmp(&b.0));
+                let mut heap = StableIntervalHeap::with_comparator(fifo_end, by_prio);
+                // Starting close to the end of the sequence numbers forces a renumbering.
+                heap.next_seq = u64::MAX - rng.gen_range(0, 10);
+                let mut all = vec![];
+                for i in 0..len {
+                    let item = (rng.gen_range(0, 5), i);
+                    heap.push(item);
+                    all.push(item);
+                    assert!(heap.heap.is_valid());
+                }
+                // Pushing in order of the second field makes it the tie breaker.
+                all.sort_by(|a, b| a.0.cmp(&b.0).then(match fifo_end {
+                    FifoEnd::Min => a.1.cmp(&b.1),
+                    FifoEnd::Max => b.1.cmp(&a.1),
+                }));
+                let mut popped = vec![];
+                let mut back = vec![];
+                while !heap.is_empty() {
+                    if rng.gen() {
+                        popped.extend(heap.pop_min());
+                    } else {
+                        back.extend(heap.pop_max());
+                    }
+                }
+                popped.extend(back.into_iter().rev());
+                assert_eq!(popped, all);
+            }
+        }
+    }
+}
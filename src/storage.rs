@@ -0,0 +1,203 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The storage backing an `IntervalHeap`.
+
+/// A growable, contiguous sequence of items that can back an
+/// [`IntervalHeap`](struct.IntervalHeap.html).
+///
+/// The heap keeps its items in the storage's slice in heap order, and only ever adds or
+/// removes items through the methods below. `Vec<T>` is the default storage; other
+/// implementations may keep their items inline, in a fixed array, or in a memory-mapped
+/// region.
+///
+/// Pushing onto a storage cannot fail, so a storage that runs out of room has to panic.
+/// A storage whose growth can fail with an error, such as a memory-mapped file that has to
+/// be resized, should have a fixed capacity and a fallible method that grows it, to be
+/// called before pushing onto the heap when [`IntervalHeap::capacity`](
+/// struct.IntervalHeap.html#method.capacity) is reached. This is how
+/// `MmapIntervalHeap`, behind the `mmap` feature, is built.
+///
+/// # Examples
+///
+/// A storage that holds at most four items inline:
+///
+/// ```
+/// use interval_heap::{HeapStorage, IntervalHeap};
+///
+/// #[derive(Default)]
+/// struct Four { items: [u32; 4], len: usize }
+///
+/// impl HeapStorage<u32> for Four {
+///     fn push(&mut self, item: u32) {
+///         self.items[self.len] = item;
+///         self.len += 1;
+///     }
+///
+///     fn pop(&mut self) -> Option<u32> {
+///         if self.len == 0 { return None; }
+///         self.len -= 1;
+///         Some(self.items[self.len])
+///     }
+///
+///     fn swap_remove(&mut self, index: usize) -> u32 {
+///         let item = self.items[index];
+///         self.items[index] = self.items[self.len - 1];
+///         self.len -= 1;
+///         item
+///     }
+///
+///     fn as_slice(&self) -> &[u32] { &self.items[..self.len] }
+///     fn as_mut_slice(&mut self) -> &mut [u32] { &mut self.items[..self.len] }
+///     fn capacity(&self) -> usize { 4 }
+/// }
+///
+/// let mut heap = IntervalHeap::from_storage(Four::default());
+/// heap.extend(&[3, 1, 4, 2]);
+/// assert_eq!(heap.min_max(), Some((&1, &4)));
+/// assert_eq!(heap.capacity(), 4);
+/// ```
+pub trait HeapStorage<T> {
+    /// Appends an item to the end of the storage.
+    ///
+    /// Storages with a fixed capacity may panic when they are full.
+    fn push(&mut self, item: T);
+
+    /// Removes the last item from the storage and returns it.
+    ///
+    /// Returns `None` if the storage is empty.
+    fn pop(&mut self) -> Option<T>;
+
+    /// Removes the item at `index` and returns it, replacing it with the last item.
+    ///
+    /// Panics if `index` is out of bounds.
+    fn swap_remove(&mut self, index: usize) -> T;
+
+    /// Returns the items in the storage.
+    fn as_slice(&self) -> &[T];
+
+    /// Returns the items in the storage for modification.
+    fn as_mut_slice(&mut self) -> &mut [T];
+
+    /// Returns the number of items the storage can hold without growing.
+    fn capacity(&self) -> usize;
+
+    /// Returns the number of items in the storage.
+    fn len(&self) -> usize { self.as_slice().len() }
+
+    /// Returns `true` if the storage contains no items.
+    fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Hints that `additional` more items are about to be pushed.
+    ///
+    /// Does nothing by default.
+    fn reserve(&mut self, additional: usize) { let _ = additional; }
+
+    /// Removes all items from the storage.
+    fn clear(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+impl<T> HeapStorage<T> for Vec<T> {
+    #[inline] fn push(&mut self, item: T) { Vec::push(self, item) }
+    #[inline] fn pop(&mut self) -> Option<T> { Vec::pop(self) }
+    #[inline] fn swap_remove(&mut self, index: usize) -> T { Vec::swap_remove(self, index) }
+    #[inline] fn as_slice(&self) -> &[T] { self }
+    #[inline] fn as_mut_slice(&mut self) -> &mut [T] { self }
+    #[inline] fn capacity(&self) -> usize { Vec::capacity(self) }
+    #[inline] fn len(&self) -> usize { Vec::len(self) }
+    #[inline] fn reserve(&mut self, additional: usize) { Vec::reserve(self, additional) }
+    #[inline] fn clear(&mut self) { Vec::clear(self) }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{thread_rng, Rng};
+    use super::super::IntervalHeap;
+    use super::HeapStorage;
+
+    /// A fixed-capacity storage keeping its items inline.
+    struct ArrayStorage<T, const N: usize> {
+        items: [T; N],
+        len: usize,
+    }
+
+    impl<T: Copy + Default, const N: usize> HeapStorage<T> for ArrayStorage<T, N> {
+        fn push(&mut self, item: T) {
+            assert!(self.len < N, "storage is full");
+            self.items[self.len] = item;
+            self.len += 1;
+        }
+
+        fn pop(&mut self) -> Option<T> {
+            if self.len == 0 { return None; }
+            self.len -= 1;
+            Some(self.items[self.len])
+        }
+
+        fn swap_remove(&mut self, index: usize) -> T {
+            assert!(index < self.len);
+            let item = self.items[index];
+            self.items[index] = self.items[self.len - 1];
+            self.len -= 1;
+            item
+        }
+
+        fn as_slice(&self) -> &[T] { &self.items[..self.len] }
+        fn as_mut_slice(&mut self) -> &mut [T] { &mut self.items[..self.len] }
+        fn capacity(&self) -> usize { N }
+    }
+
+    #[test]
+    fn fuzz_array_storage() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let storage = ArrayStorage { items: [0u32; 32], len: 0 };
+            let mut heap = IntervalHeap::from_storage(storage);
+            let mut model = IntervalHeap::new();
+            for _ in 0..200 {
+                match rng.gen_range(0, 4) {
+                    _ if heap.len() == heap.capacity() => {
+                        assert_eq!(heap.pop_max(), model.pop_max());
+                    }
+                    0 => assert_eq!(heap.pop_min(), model.pop_min()),
+                    1 => assert_eq!(heap.pop_max(), model.pop_max()),
+                    _ => {
+                        let item = rng.gen_range(0, 100);
+                        heap.push(item);
+                        model.push(item);
+                    }
+                }
+                assert_eq!(heap.data.as_slice(), &model.data[..]);
+            }
+            let storage = heap.into_storage();
+            assert_eq!(storage.as_slice(), &model.data[..]);
+        }
+    }
+
+    #[test]
+    fn test_from_storage_heapifies() {
+        let mut rng = thread_rng();
+        for len in 0..33 {
+            let mut storage = ArrayStorage { items: [0u32; 32], len: 0 };
+            let mut vec = vec![];
+            for _ in 0..len {
+                let item = rng.gen_range(0, 100);
+                storage.push(item);
+                vec.push(item);
+            }
+            let heap = IntervalHeap::from_storage(storage);
+            assert!(heap.is_valid());
+            vec.sort();
+            assert_eq!(heap.into_iter_sorted().collect::<Vec<_>>(), vec);
+        }
+    }
+}